@@ -0,0 +1,66 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use face_landmark_mp::image_util;
+use std::*;
+
+fn bench<F: FnMut()>(name: &str, n: usize, mut f: F) -> time::Duration {
+    f();
+    let t = time::Instant::now();
+    for _ in 0..n {
+        f();
+    }
+    let dt = t.elapsed() / n as u32;
+    println!("{:<32} {:>10.1?}", name, dt);
+    dt
+}
+
+// the former implementation, as the reference: get_pixel per channel with a bounds test on every element.
+fn convert_with_padding_old<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+    dst: &mut [f32], size: usize, src: &I,
+) -> (usize, usize, usize, usize) {
+    let y0 = (1 + size - src.height() as usize) / 2;
+    let x0 = (1 + size - src.width() as usize) / 2;
+    let y1 = y0 + src.height() as usize;
+    let x1 = x0 + src.width() as usize;
+    for y in 0..size {
+        for x in 0..size {
+            for c in 0..3 {
+                dst[(3 * size) * y + 3 * x + c] = if y0 <= y && y < y1 && x0 <= x && x < x1 {
+                    src.get_pixel((x - x0) as u32, (y - y0) as u32)[c] as f32 / 255.0 - 0.5
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+    (y0, x0, y1, x1)
+}
+
+fn main() {
+    for &size in [128, 192].iter() {
+        // 16:9, letterboxed.
+        let src = image::RgbImage::from_fn(size as u32, (size * 9 / 16) as u32, |x, y| {
+            image::Rgb([x as u8, y as u8, (x ^ y) as u8])
+        });
+        let view = image::GenericImageView::view(&src, 0, 0, src.width(), src.height());
+        let mut dst = vec![0.0; 3 * size * size];
+        let normalization = image_util::Normalization::default();
+
+        // RgbImage, as the resized image.
+        let old = bench(&format!("old {}", size), 1000, || {
+            convert_with_padding_old(&mut dst, size, &src);
+        });
+        let fast = bench(&format!("contiguous {}", size), 1000, || {
+            image_util::convert_rgb_with_padding(&mut dst, size, &src, &normalization, [127.5; 3]);
+        });
+        println!("speedup {:.1}x", old.as_secs_f64() / fast.as_secs_f64());
+
+        // a generic view, as the image of the input size.
+        let old = bench(&format!("old view {}", size), 1000, || {
+            convert_with_padding_old(&mut dst, size, &*view);
+        });
+        let generic = bench(&format!("generic view {}", size), 1000, || {
+            image_util::convert_with_padding(&mut dst, size, &*view, &normalization, [127.5; 3]);
+        });
+        println!("speedup {:.1}x", old.as_secs_f64() / generic.as_secs_f64());
+    }
+}
@@ -5,36 +5,34 @@ use std::*;
 
 fn main() -> Result<(), Box<dyn error::Error>> {
     let mut capture = opencv::videoio::VideoCapture::new(0, 0)?;
-    let face_loop = face_loop::FaceLoop::new();
+    let mut face_loop = face_loop::FaceLoop::with_config(face_loop::FaceLoopConfig {
+        mode: face_loop::Mode::Video,
+        ..Default::default()
+    });
     loop {
         let mut src = opencv::core::Mat::default()?;
         capture.read(&mut src)?;
 
-        let mut img = image::RgbImage::from_raw(
-            src.cols() as u32,
-            src.rows() as u32,
-            src.reshape(1, 1)?.data_typed()?.to_vec(),
-        ).unwrap();
-        swap_rgb(&mut img);
+        // BGR as captured, without copying.
+        let (w, h) = (src.cols(), src.rows());
+        let frame = frame::Frame::with_stride(
+            src.data_bytes()?,
+            w as u32,
+            h as u32,
+            src.step1(0)?,
+            frame::PixelFormat::Bgr,
+        );
 
-        let (landmarks, landmarks_likelihood) = face_loop.run(&img);
-        dbg!(landmarks_likelihood);
-        for (x, y, _) in landmarks.iter() {
-            if 0.0 <= *x && *x < img.width() as f32 && 0.0 <= *y && *y < img.height() as f32 {
-                img[(*x as u32, *y as u32)] = image::Rgb([0, 255, 255]);
+        for landmarks in face_loop.run(&frame).iter() {
+            dbg!(landmarks.presence);
+            for p in landmarks.points.iter() {
+                if 0.0 <= p.x && p.x < w as f32 && 0.0 <= p.y && p.y < h as f32 {
+                    *src.at_2d_mut::<opencv::core::Vec3b>(p.y as i32, p.x as i32)? = [255, 255, 0].into();
+                }
             }
         }
 
-        swap_rgb(&mut img);
-        let dst = Mat::from_slice(&img.to_vec())?.reshape(3, src.rows())?;
-
-        opencv::highgui::imshow("camera", &dst)?;
+        opencv::highgui::imshow("camera", &src)?;
         opencv::highgui::wait_key(1)?;
     }
 }
-
-fn swap_rgb(img: &mut image::RgbImage) {
-    for px in img.pixels_mut() {
-        px.0.swap(0, 2);
-    }
-}
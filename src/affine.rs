@@ -0,0 +1,226 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::face_detector::BBox;
+use nalgebra::{Matrix2, Matrix3, Point2, Point3, Vector2};
+use std::*;
+
+// p -> linear * p + translation on (x, y).  a * b applies b first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Affine2 {
+    pub linear: Matrix2<f32>,
+    pub translation: Vector2<f32>,
+}
+
+impl Affine2 {
+    pub fn new(linear: Matrix2<f32>, translation: Vector2<f32>) -> Self {
+        Affine2 {
+            linear: linear,
+            translation: translation,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Matrix2::identity(), Vector2::zeros())
+    }
+
+    pub fn from_translation(x: f32, y: f32) -> Self {
+        Self::new(Matrix2::identity(), Vector2::new(x, y))
+    }
+
+    pub fn from_scale(x: f32, y: f32) -> Self {
+        Self::new(Matrix2::new(x, 0.0, 0.0, y), Vector2::zeros())
+    }
+
+    // from x to y, i.e. clockwise in the image.
+    pub fn from_rotation(angle: f32) -> Self {
+        let (sin, cos) = f32::sin_cos(angle);
+        Self::new(Matrix2::new(cos, -sin, sin, cos), Vector2::zeros())
+    }
+
+    // of (x, y, 1).
+    pub fn from_matrix(m: &Matrix3<f32>) -> Self {
+        Self::new(m.fixed_view::<2, 2>(0, 0).into(), m.fixed_view::<2, 1>(0, 2).into())
+    }
+
+    pub fn to_matrix(&self) -> Matrix3<f32> {
+        let mut m = Matrix3::identity();
+        m.fixed_view_mut::<2, 2>(0, 0).copy_from(&self.linear);
+        m.fixed_view_mut::<2, 1>(0, 2).copy_from(&self.translation);
+        m
+    }
+
+    // self after other.
+    pub fn compose(&self, other: &Affine2) -> Affine2 {
+        Self::new(
+            self.linear * other.linear,
+            self.linear * other.translation + self.translation,
+        )
+    }
+
+    pub fn try_inverse(&self) -> Option<Affine2> {
+        let linear = self.linear.try_inverse()?;
+        Some(Self::new(linear, -(linear * self.translation)))
+    }
+
+    // panics if singular.
+    pub fn inverse(&self) -> Affine2 {
+        self.try_inverse().expect("singular affine transform")
+    }
+
+    pub fn point(&self, p: &Point2<f32>) -> Point2<f32> {
+        Point2::from(self.linear * p.coords + self.translation)
+    }
+
+    pub fn vector(&self, v: &Vector2<f32>) -> Vector2<f32> {
+        self.linear * v
+    }
+
+    // the length of the image of a unit x vector, by which z of landmarks is scaled.
+    pub fn scale(&self) -> f32 {
+        f32::hypot(self.linear[(0, 0)], self.linear[(1, 0)])
+    }
+
+    // applies to (x, y), and the scale to z.
+    pub fn point3(&self, p: &Point3<f32>) -> Point3<f32> {
+        let q = self.point(&p.xy());
+        Point3::new(q.x, q.y, self.scale() * p.z)
+    }
+
+    // a point in (y, x) order, as BBox and roi::Roi.
+    pub fn point_yx(&self, (y, x): (f32, f32)) -> (f32, f32) {
+        let q = self.point(&Point2::new(x, y));
+        (q.y, q.x)
+    }
+
+    // the size is of the bounding box of the transformed box, which is exact unless rotated by other than
+    // multiples of 90 degrees.
+    pub fn bbox(&self, bbox: &BBox) -> BBox {
+        let mut key_points = bbox.key_points;
+        for p in key_points.iter_mut() {
+            *p = self.point_yx(*p);
+        }
+        let size = self.linear.abs() * Vector2::new(bbox.size.1, bbox.size.0);
+        BBox {
+            center: self.point_yx(bbox.center),
+            size: (size.y, size.x),
+            key_points: key_points,
+            score: bbox.score,
+        }
+    }
+}
+
+impl Default for Affine2 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl ops::Mul for Affine2 {
+    type Output = Affine2;
+
+    fn mul(self, other: Affine2) -> Affine2 {
+        self.compose(&other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a linear congruential generator in [0, 1), for reproducible compositions.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next()
+        }
+
+        fn affine(&mut self) -> Affine2 {
+            let mut a = Affine2::identity();
+            for _ in 0..1 + (4.0 * self.next()) as usize {
+                let t = match (3.0 * self.next()) as usize {
+                    0 => Affine2::from_translation(self.range(-100.0, 100.0), self.range(-100.0, 100.0)),
+                    1 => Affine2::from_rotation(self.range(-4.0, 4.0)),
+                    _ => {
+                        let sign = if self.next() < 0.5 { -1.0 } else { 1.0 };
+                        Affine2::from_scale(sign * self.range(0.5, 2.0), self.range(0.5, 2.0))
+                    }
+                };
+                a = t * a;
+            }
+            a
+        }
+    }
+
+    fn assert_close(a: &Affine2, b: &Affine2) {
+        assert!((a.linear - b.linear).amax() < 1e-4, "{:?} != {:?}", a, b);
+        assert!((a.translation - b.translation).amax() < 1e-2, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut random = Random(1);
+        for _ in 0..1000 {
+            let a = random.affine();
+            let inv = a.inverse();
+            assert_close(&(inv * a), &Affine2::identity());
+            assert_close(&(a * inv), &Affine2::identity());
+            assert_eq!(Affine2::from_matrix(&a.to_matrix()), a);
+
+            let p = Point2::new(random.range(-500.0, 500.0), random.range(-500.0, 500.0));
+            assert!((a.point(&inv.point(&p)) - p).amax() < 1e-2);
+            assert!((a.to_matrix() * p.to_homogeneous()).xy() - a.point(&p).coords == Vector2::zeros());
+        }
+    }
+
+    #[test]
+    fn compose() {
+        let mut random = Random(2);
+        for _ in 0..1000 {
+            let (a, b, c) = (random.affine(), random.affine(), random.affine());
+            assert_close(&((a * b) * c), &(a * (b * c)));
+            let p = Point2::new(random.range(-10.0, 10.0), random.range(-10.0, 10.0));
+            assert!(((a * b).point(&p) - a.point(&b.point(&p))).amax() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn bbox() {
+        let bbox = BBox {
+            center: (20.0, 10.0),
+            size: (8.0, 6.0),
+            key_points: [
+                (18.0, 8.0),
+                (18.0, 12.0),
+                (20.0, 10.0),
+                (22.0, 10.0),
+                (20.0, 7.0),
+                (20.0, 13.0),
+            ],
+            score: 0.9,
+        };
+        let rot90 = Affine2::new(Matrix2::new(0.0, -1.0, 1.0, 0.0), Vector2::new(100.0, 0.0));
+        let mirror = Affine2::from_translation(100.0, 0.0) * Affine2::from_scale(-1.0, 1.0);
+        for (t, size) in [(rot90, (6.0, 8.0)), (mirror, (8.0, 6.0)), (rot90 * mirror, (6.0, 8.0))].iter() {
+            let b = t.bbox(&bbox);
+            assert_eq!(b.size, *size);
+            assert_eq!(b.center, t.point_yx(bbox.center));
+            for (p, q) in b.key_points.iter().zip(bbox.key_points.iter()) {
+                assert_eq!(*p, t.point_yx(*q));
+            }
+            assert_eq!(b.score, bbox.score);
+        }
+        // x -> y, i.e. clockwise.
+        let b = Affine2::from_rotation(f32::consts::FRAC_PI_2).bbox(&bbox);
+        assert!((b.center.0 - 10.0).abs() < 1e-5 && (b.center.1 + 20.0).abs() < 1e-5);
+        assert!((b.size.0 - 6.0).abs() < 1e-5 && (b.size.1 - 8.0).abs() < 1e-5);
+    }
+}
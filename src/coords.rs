@@ -0,0 +1,47 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use crate::face_detector;
+use crate::face_landmark;
+use nalgebra::Point3;
+use std::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordinateSpace {
+    // pixels of the source image.  z has the same scale as x.
+    #[default]
+    Pixel,
+    // x / width, y / height in [0, 1] and z / width, as MediaPipe.
+    Normalized,
+    // Normalized with x flipped, for selfie views.
+    Mirrored,
+    // pixels with the origin at the image center, i.e. the principal point of a typical camera.
+    Centered,
+}
+
+impl CoordinateSpace {
+    // from pixels to this space.  z is scaled as x.  image_size is (height, width).
+    pub fn transform(&self, image_size: (f32, f32)) -> Affine2 {
+        let (h, w) = image_size;
+        match *self {
+            CoordinateSpace::Pixel => Affine2::identity(),
+            CoordinateSpace::Normalized => Affine2::from_scale(1.0 / w, 1.0 / h),
+            CoordinateSpace::Mirrored => Affine2::from_translation(1.0, 0.0) * Affine2::from_scale(-1.0 / w, 1.0 / h),
+            CoordinateSpace::Centered => Affine2::from_translation(-w / 2.0, -h / 2.0),
+        }
+    }
+
+    pub fn point(&self, p: &Point3<f32>, image_size: (f32, f32)) -> Point3<f32> {
+        self.transform(image_size).point3(p)
+    }
+
+    pub fn bbox(&self, bbox: &face_detector::BBox, image_size: (f32, f32)) -> face_detector::BBox {
+        self.transform(image_size).bbox(bbox)
+    }
+
+    pub fn landmarks(
+        &self, landmarks: &face_landmark::FaceLandmarks, image_size: (f32, f32),
+    ) -> face_landmark::FaceLandmarks {
+        landmarks.transformed(&self.transform(image_size))
+    }
+}
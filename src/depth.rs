@@ -0,0 +1,64 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+//
+// ref. <https://github.com/google/mediapipe/blob/master/mediapipe/graphs/iris_tracking/calculators/iris_to_depth_calculator.cc>.
+use crate::iris_landmark;
+use nalgebra::Point3;
+use std::*;
+
+// the horizontal iris diameter is nearly constant among humans, 11.7 +- 0.5 mm.
+pub const IRIS_DIAMETER_MM: f32 = 11.7;
+
+#[derive(Clone, Copy, Debug)]
+pub enum FocalLength {
+    Pixels(f32),
+    // horizontal field of view in degrees.
+    HorizontalFov(f32),
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Depth {
+    // distance from the camera in mm, averaged over both eyes.
+    pub distance: f32,
+    // [right, left] as iris_landmark::IrisLandmark::run_with_face.
+    pub eyes: [f32; 2],
+    // mm per pixel around the face.
+    pub scale: f32,
+}
+
+impl FocalLength {
+    pub fn pixels(&self, image_width: f32) -> f32 {
+        match *self {
+            FocalLength::Pixels(f) => f,
+            FocalLength::HorizontalFov(fov) => image_width / 2.0 / f32::tan(fov.to_radians() / 2.0),
+        }
+    }
+}
+
+impl Depth {
+    // approximate metric depth of a relative z of face_landmark::FaceLandmark, which is in pixels.
+    pub fn metric_z(&self, z: f32) -> f32 {
+        self.distance + self.scale * z
+    }
+}
+
+// image_size is (height, width).
+pub fn estimate(eyes: &[iris_landmark::Eye; 2], image_size: (f32, f32), focal_length: FocalLength) -> Depth {
+    let focal = focal_length.pixels(image_size.1);
+    let origin = (image_size.1 / 2.0, image_size.0 / 2.0);
+
+    let mut depths = [0.0; 2];
+    let mut diameters = [0.0; 2];
+    for (i, eye) in eyes.iter().enumerate() {
+        let dist = |p0: Point3<f32>, p1: Point3<f32>| f32::hypot(p1.x - p0.x, p1.y - p0.y);
+        diameters[i] = (dist(eye.iris[1], eye.iris[3]) + dist(eye.iris[2], eye.iris[4])) / 2.0;
+        let r = f32::hypot(eye.iris[0].x - origin.0, eye.iris[0].y - origin.1);
+        depths[i] = IRIS_DIAMETER_MM * f32::hypot(focal, r) / diameters[i];
+    }
+
+    Depth {
+        distance: (depths[0] + depths[1]) / 2.0,
+        eyes: depths,
+        scale: 2.0 * IRIS_DIAMETER_MM / (diameters[0] + diameters[1]),
+    }
+}
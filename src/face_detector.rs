@@ -1,10 +1,15 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use crate::coords;
 use crate::image_util;
+use crate::orientation;
 use crate::ssd;
 use crate::tflite;
+use nalgebra::Vector2;
 use std::*;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BBox {
     pub center: (f32, f32),
     pub size: (f32, f32),
@@ -12,14 +17,68 @@ pub struct BBox {
     pub score: f32,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    Score,
+    Area,
+    CenterDistance,
+}
+
+#[derive(Clone, Debug)]
+pub struct DetectorConfig {
+    pub min_score: f32,
+    // IoU above which a lower-scored box is suppressed.
+    pub nms_threshold: f32,
+    // Bounds of the longer box side, in pixels.
+    pub min_size: f32,
+    pub max_size: f32,
+    // Bounds of width / height.
+    pub min_aspect: f32,
+    pub max_aspect: f32,
+    pub max_faces: usize,
+    pub order: Order,
+    // of the output.  the filters above are in pixels regardless.
+    pub space: coords::CoordinateSpace,
+    // of the model input, from the model metadata if None.
+    pub normalization: Option<image_util::Normalization>,
+    pub resize: image_util::ResizeConfig,
+    // of the input, which is made upright before the model.  the output is in the input regardless.
+    pub orientation: orientation::Orientation,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        DetectorConfig {
+            min_score: 0.5,
+            nms_threshold: 0.3,
+            min_size: 0.0,
+            max_size: f32::INFINITY,
+            min_aspect: 0.0,
+            max_aspect: f32::INFINITY,
+            max_faces: usize::MAX,
+            order: Order::Score,
+            space: coords::CoordinateSpace::Pixel,
+            normalization: None,
+            resize: image_util::ResizeConfig::default(),
+            orientation: orientation::Orientation::default(),
+        }
+    }
+}
+
 pub struct FaceDetector {
+    config: DetectorConfig,
     size: usize,
+    normalization: image_util::Normalization,
     anchors: Vec<ssd::Anchor>,
     interp: tflite::Interpreter,
 }
 
 impl FaceDetector {
     pub fn new() -> Self {
+        Self::with_config(DetectorConfig::default())
+    }
+
+    pub fn with_config(config: DetectorConfig) -> Self {
         let size = 128;
         let anchors = ssd::generate(&ssd::Options {
             input_size_width: size,
@@ -39,40 +98,88 @@ impl FaceDetector {
         });
         assert_eq!(anchors.len(), 896);
 
-        let interp = tflite::Interpreter::new(include_bytes!("../models/face_detection_front.tflite"));
+        let model = include_bytes!("../models/face_detection_front.tflite");
+        let interp = tflite::Interpreter::new(model);
+        let normalization = config
+            .normalization
+            .or_else(|| image_util::Normalization::from_model(model))
+            .unwrap_or_default();
 
         FaceDetector {
+            config: config,
             size: size,
+            normalization: normalization,
             anchors: anchors,
             interp: interp,
         }
     }
 
     pub fn run<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(&self, image: &I) -> Vec<BBox> {
+        self.run_oriented(image, self.config.orientation)
+    }
+
+    // overrides DetectorConfig::orientation, e.g. with orientation::Orientation::from_exif().
+    pub fn run_oriented<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, orientation: orientation::Orientation,
+    ) -> Vec<BBox> {
+        let bboxes = if orientation.is_identity() {
+            self.run_upright(image)
+        } else {
+            let view = orientation::Oriented::new(image, orientation);
+            let transform = view.transform();
+            self.run_upright(&view).iter().map(|b| transform.bbox(b)).collect()
+        };
+        let image_size = (image.height() as f32, image.width() as f32);
+        match self.config.space {
+            coords::CoordinateSpace::Pixel => bboxes,
+            space => bboxes.iter().map(|b| space.bbox(b, image_size)).collect(),
+        }
+    }
+
+    pub fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    // the one in effect, i.e. DetectorConfig::normalization, the model metadata or the default.
+    pub fn normalization(&self) -> &image_util::Normalization {
+        &self.normalization
+    }
+
+    // in pixels.
+    fn run_upright<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(&self, image: &I) -> Vec<BBox> {
         let inputs = self.interp.inputs();
-        let transform = image_util::resize_keeping_aspect(inputs[0].data_mut(), self.size, image);
+        let transform = image_util::resize(
+            inputs[0].data_mut(),
+            self.size,
+            image,
+            &self.config.resize,
+            &self.normalization,
+        );
 
         self.interp.invoke();
 
         let outputs = self.interp.outputs();
         let boxes: &[f32] = outputs[0].data();
         let scores: &[f32] = outputs[1].data();
-        self.decode_output(boxes, scores, &transform)
+        let image_size = (image.height() as f32, image.width() as f32);
+        self.filter(self.decode_output(boxes, scores, &transform), image_size)
     }
 
     // ref. <https://github.com/google/mediapipe/blob/master/mediapipe/modules/face_detection/face_detection_front_cpu.pbtxt>.
     // ref. <https://github.com/google/mediapipe/blob/master/mediapipe/calculators/tflite/tflite_tensors_to_detections_calculator.cc>.
-    fn decode_output(&self, boxes: &[f32], scores: &[f32], transform: &image_util::Transform) -> Vec<BBox> {
+    fn decode_output(&self, boxes: &[f32], scores: &[f32], transform: &Affine2) -> Vec<BBox> {
         let mut dst = Vec::new();
         for (i, anchor) in self.anchors.iter().enumerate() {
-            let ay = anchor.size.0 * transform.ay;
-            let ax = anchor.size.1 * transform.ax;
-            let by = anchor.center.0 * (self.size as f32 * transform.ay) + transform.by;
-            let bx = anchor.center.1 * (self.size as f32 * transform.ax) + transform.bx;
-            let translate = |y, x| (ay * y + by, ax * x + bx);
+            // from the output of the anchor to the source image.
+            let s = self.size as f32;
+            let t = *transform
+                * Affine2::from_translation(anchor.center.1 * s, anchor.center.0 * s)
+                * Affine2::from_scale(anchor.size.1, anchor.size.0);
+            let translate = |y, x| t.point_yx((y, x));
+            let size = t.vector(&Vector2::new(boxes[16 * i + 2], boxes[16 * i + 3]));
             dst.push(BBox {
                 center: translate(boxes[16 * i + 1], boxes[16 * i + 0]),
-                size: (ay * boxes[16 * i + 3], ax * boxes[16 * i + 2]),
+                size: (size.y, size.x),
                 key_points: [
                     translate(boxes[16 * i + 5], boxes[16 * i + 4]),
                     translate(boxes[16 * i + 7], boxes[16 * i + 6]),
@@ -86,4 +193,58 @@ impl FaceDetector {
         }
         dst
     }
+
+    fn filter(&self, mut bboxes: Vec<BBox>, image_size: (f32, f32)) -> Vec<BBox> {
+        let config = &self.config;
+        bboxes.retain(|b| {
+            let size = f32::max(b.size.0, b.size.1);
+            let aspect = b.size.1 / b.size.0;
+            config.min_score <= b.score
+                && config.min_size <= size
+                && size <= config.max_size
+                && config.min_aspect <= aspect
+                && aspect <= config.max_aspect
+        });
+
+        // non-maximum suppression.
+        bboxes.sort_by(|e0, e1| e1.score.partial_cmp(&e0.score).unwrap());
+        let mut dst: Vec<BBox> = Vec::new();
+        for bbox in bboxes.into_iter() {
+            if dst
+                .iter()
+                .all(|e| iou(e.center, e.size, bbox.center, bbox.size) <= config.nms_threshold)
+            {
+                dst.push(bbox);
+            }
+        }
+
+        let center = (image_size.0 / 2.0, image_size.1 / 2.0);
+        match config.order {
+            Order::Score => (),
+            Order::Area => dst.sort_by(|e0, e1| (e1.size.0 * e1.size.1).partial_cmp(&(e0.size.0 * e0.size.1)).unwrap()),
+            Order::CenterDistance => {
+                let dist = |b: &BBox| f32::hypot(b.center.0 - center.0, b.center.1 - center.1);
+                dst.sort_by(|e0, e1| dist(e0).partial_cmp(&dist(e1)).unwrap())
+            }
+        }
+        dst.truncate(config.max_faces);
+        dst
+    }
+}
+
+// of axis-aligned boxes given by (center, size), in (y, x) order as BBox.  shared with face_loop and tracker.
+pub fn iou(c0: (f32, f32), s0: (f32, f32), c1: (f32, f32), s1: (f32, f32)) -> f32 {
+    let overlap = |c0: f32, s0: f32, c1: f32, s1: f32| {
+        f32::max(
+            0.0,
+            f32::min(c0 + s0 / 2.0, c1 + s1 / 2.0) - f32::max(c0 - s0 / 2.0, c1 - s1 / 2.0),
+        )
+    };
+    let i = overlap(c0.0, s0.0, c1.0, s1.0) * overlap(c0.1, s0.1, c1.1, s1.1);
+    let u = s0.0 * s0.1 + s1.0 * s1.1 - i;
+    if u > 0.0 {
+        i / u
+    } else {
+        0.0
+    }
 }
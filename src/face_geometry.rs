@@ -0,0 +1,351 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+//
+// almost 1:1 translation of:
+//     mediapipe/modules/face_geometry/libs/geometry_pipeline.cc
+//     mediapipe/modules/face_geometry/libs/procrustes_solver.cc
+//     Copyright 2020 The MediaPipe Authors, under Apache License v2.0.
+use crate::face_landmark;
+use nalgebra::{Matrix3, Matrix3xX, Matrix4, Point3, Vector3};
+use std::*;
+
+// the canonical model is not bundled; load mediapipe/modules/face_geometry/data/canonical_face_model.obj by
+// from_obj, and its weights by set_weights_from_pbtxt, without which the pose differs from MediaPipe.
+#[derive(Clone, Debug)]
+pub struct CanonicalModel {
+    pub vertices: Vec<Point3<f32>>,
+    pub weights: Vec<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PerspectiveCamera {
+    // in degrees.
+    pub vertical_fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceGeometry {
+    // from the canonical face space to the camera space, in cm as the canonical model.
+    pub pose: Matrix4<f32>,
+    // the metric landmarks in the canonical face space.
+    pub mesh: Vec<Point3<f32>>,
+}
+
+pub struct GeometryPipeline {
+    model: CanonicalModel,
+    camera: PerspectiveCamera,
+}
+
+struct Frustum {
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+}
+
+impl Default for PerspectiveCamera {
+    fn default() -> Self {
+        PerspectiveCamera {
+            vertical_fov: 63.0,
+            near: 1.0,
+            far: 10000.0,
+        }
+    }
+}
+
+impl CanonicalModel {
+    // parses "v x y z" lines of a wavefront .obj, with uniform weights.
+    pub fn from_obj(src: &str) -> Option<Self> {
+        let mut vertices = Vec::new();
+        for line in src.lines() {
+            let mut it = line.split_whitespace();
+            if it.next() != Some("v") {
+                continue;
+            }
+            let mut v = [0.0; 3];
+            for e in v.iter_mut() {
+                *e = it.next()?.parse().ok()?;
+            }
+            vertices.push(Point3::new(v[0], v[1], v[2]));
+        }
+        let weights = vec![1.0; vertices.len()];
+        Some(CanonicalModel {
+            vertices: vertices,
+            weights: weights,
+        })
+    }
+
+    // sets the weights from procrustes_landmark_basis entries of a geometry_pipeline_metadata text proto, e.g.
+    // mediapipe/modules/face_geometry/data/geometry_pipeline_metadata_landmarks.pbtxt.
+    pub fn set_weights_from_pbtxt(&mut self, src: &str) -> Option<()> {
+        let mut basis = Vec::new();
+        for entry in src.split("procrustes_landmark_basis").skip(1) {
+            let entry = &entry[..entry.find('}')?];
+            let value = |key: &str| {
+                let rest = &entry[entry.find(key)? + key.len()..];
+                rest.trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+                    .split(|c: char| c.is_whitespace() || c == '}')
+                    .next()
+            };
+            let id: usize = value("landmark_id")?.parse().ok()?;
+            let weight: f32 = value("weight")?.parse().ok()?;
+            if id >= self.vertices.len() {
+                return None;
+            }
+            basis.push((id, weight));
+        }
+        if basis.is_empty() {
+            return None;
+        }
+        self.set_weights(&basis);
+        Some(())
+    }
+
+    // sparse (landmark id, weight) pairs as procrustes_landmark_basis in geometry_pipeline_metadata.
+    pub fn set_weights(&mut self, basis: &[(usize, f32)]) {
+        self.weights = vec![0.0; self.vertices.len()];
+        for (i, w) in basis.iter() {
+            self.weights[*i] = *w;
+        }
+    }
+}
+
+impl GeometryPipeline {
+    pub fn new(model: CanonicalModel, camera: PerspectiveCamera) -> Self {
+        GeometryPipeline {
+            model: model,
+            camera: camera,
+        }
+    }
+
+    // image_size is (height, width).
+    pub fn run(&self, landmarks: &face_landmark::FaceLandmarks, image_size: (f32, f32)) -> FaceGeometry {
+        assert_eq!(landmarks.points.len(), self.model.vertices.len());
+
+        let height_at_near = 2.0 * self.camera.near * f32::tan(self.camera.vertical_fov.to_radians() / 2.0);
+        let width_at_near = height_at_near * image_size.1 / image_size.0;
+        let frustum = Frustum {
+            left: -width_at_near / 2.0,
+            right: width_at_near / 2.0,
+            bottom: -height_at_near / 2.0,
+            top: height_at_near / 2.0,
+            near: self.camera.near,
+        };
+
+        let canonical = Matrix3xX::from_fn(self.model.vertices.len(), |r, c| self.model.vertices[c][r]);
+        let mut screen = Matrix3xX::from_fn(landmarks.points.len(), |r, c| {
+            let p = landmarks.points[c];
+            [p.x / image_size.1, p.y / image_size.0, p.z / image_size.1][r]
+        });
+        project_xy(&frustum, &mut screen);
+        let depth_offset = screen.row(2).mean();
+
+        // 1st iteration: don't unproject XY because it's unsafe to do so due to the relative nature of the Z
+        // coordinate. Instead, run the first estimation on the projected XY and use that scale to unproject for
+        // the 2nd iteration.
+        let mut intermediate = screen.clone();
+        move_and_rescale_z(&frustum, depth_offset, 1.0, &mut intermediate);
+        let first_scale = self.estimate_scale(&canonical, &intermediate);
+
+        let mut intermediate = screen.clone();
+        move_and_rescale_z(&frustum, depth_offset, first_scale, &mut intermediate);
+        unproject_xy(&frustum, &mut intermediate);
+        change_handedness(&mut intermediate);
+        let second_scale = self.estimate_scale(&canonical, &intermediate);
+
+        let mut metric = screen;
+        move_and_rescale_z(&frustum, depth_offset, first_scale * second_scale, &mut metric);
+        unproject_xy(&frustum, &mut metric);
+        change_handedness(&mut metric);
+
+        let pose = solve_weighted_orthogonal_problem(&canonical, &metric, &self.model.weights);
+        let inv_pose = pose.try_inverse().unwrap();
+        let mesh = metric
+            .column_iter()
+            .map(|c| inv_pose.transform_point(&Point3::new(c[0], c[1], c[2])))
+            .collect();
+
+        FaceGeometry { pose: pose, mesh: mesh }
+    }
+
+    fn estimate_scale(&self, canonical: &Matrix3xX<f32>, landmarks: &Matrix3xX<f32>) -> f32 {
+        let transform = solve_weighted_orthogonal_problem(canonical, landmarks, &self.model.weights);
+        transform.fixed_view::<3, 1>(0, 0).norm()
+    }
+}
+
+fn project_xy(f: &Frustum, landmarks: &mut Matrix3xX<f32>) {
+    let x_scale = f.right - f.left;
+    let y_scale = f.top - f.bottom;
+    for mut c in landmarks.column_iter_mut() {
+        c[0] = c[0] * x_scale + f.left;
+        c[1] = (1.0 - c[1]) * y_scale + f.bottom;
+        c[2] *= x_scale;
+    }
+}
+
+fn move_and_rescale_z(f: &Frustum, depth_offset: f32, scale: f32, landmarks: &mut Matrix3xX<f32>) {
+    for mut c in landmarks.column_iter_mut() {
+        c[2] = (c[2] - depth_offset + f.near) / scale;
+    }
+}
+
+fn unproject_xy(f: &Frustum, landmarks: &mut Matrix3xX<f32>) {
+    for mut c in landmarks.column_iter_mut() {
+        c[0] *= c[2] / f.near;
+        c[1] *= c[2] / f.near;
+    }
+}
+
+fn change_handedness(landmarks: &mut Matrix3xX<f32>) {
+    for mut c in landmarks.column_iter_mut() {
+        c[2] = -c[2];
+    }
+}
+
+// finds the similarity transform minimizing sum_i w_i |targets_i - (s R sources_i + t)|^2.
+fn solve_weighted_orthogonal_problem(
+    sources: &Matrix3xX<f32>, targets: &Matrix3xX<f32>, weights: &[f32],
+) -> Matrix4<f32> {
+    let sqrt_weights: Vec<f32> = weights.iter().map(|w| f32::sqrt(*w)).collect();
+    let total_weight: f32 = weights.iter().sum();
+    let weighted_sources = Matrix3xX::from_fn(sources.ncols(), |r, c| sources[(r, c)] * sqrt_weights[c]);
+    let weighted_targets = Matrix3xX::from_fn(targets.ncols(), |r, c| targets[(r, c)] * sqrt_weights[c]);
+
+    let mut source_center_of_mass = Vector3::zeros();
+    for (c, w) in weighted_sources.column_iter().zip(sqrt_weights.iter()) {
+        source_center_of_mass += c * *w;
+    }
+    source_center_of_mass /= total_weight;
+    let centered_weighted_sources = Matrix3xX::from_fn(sources.ncols(), |r, c| {
+        weighted_sources[(r, c)] - source_center_of_mass[r] * sqrt_weights[c]
+    });
+
+    let design_matrix = &weighted_targets * centered_weighted_sources.transpose();
+    let rotation = compute_optimal_rotation(design_matrix);
+
+    let rotated_centered_weighted_sources = rotation * &centered_weighted_sources;
+    let numerator = rotated_centered_weighted_sources.component_mul(&weighted_targets).sum();
+    let denominator = centered_weighted_sources.component_mul(&weighted_sources).sum();
+    let scale = numerator / denominator;
+
+    let rotation_and_scale = scale * rotation;
+    let pointwise_diffs = &weighted_targets - rotation_and_scale * &weighted_sources;
+    let mut translation = Vector3::zeros();
+    for (c, w) in pointwise_diffs.column_iter().zip(sqrt_weights.iter()) {
+        translation += c * *w;
+    }
+    translation /= total_weight;
+
+    let mut transform = rotation_and_scale.to_homogeneous();
+    transform.fixed_view_mut::<3, 1>(0, 3).copy_from(&translation);
+    transform
+}
+
+fn compute_optimal_rotation(design_matrix: Matrix3<f32>) -> Matrix3<f32> {
+    let svd = design_matrix.svd(true, true);
+    let mut postrotation = svd.u.unwrap();
+    let prerotation = svd.v_t.unwrap();
+    // reflection to rotation.
+    if postrotation.determinant() * prerotation.determinant() < 0.0 {
+        postrotation.column_mut(2).neg_mut();
+    }
+    postrotation * prerotation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::affine::Affine2;
+    use nalgebra::{Rotation3, Translation3};
+
+    // a face-sized ellipsoid in cm, in place of the canonical model.
+    fn model() -> CanonicalModel {
+        let vertices = (0..468)
+            .map(|i| {
+                let z = 1.0 - 2.0 * (i as f32 + 0.5) / 468.0;
+                let r = f32::sqrt(1.0 - z * z);
+                let a = 2.4 * i as f32;
+                Point3::new(7.0 * r * a.cos(), 9.0 * z, 5.0 * r * a.sin())
+            })
+            .collect();
+        let mut model = CanonicalModel {
+            vertices: vertices,
+            weights: Vec::new(),
+        };
+        // sparse as procrustes_landmark_basis.
+        let basis: Vec<_> = (0..468).step_by(7).map(|i| (i, 0.5 + (i % 3) as f32)).collect();
+        model.set_weights(&basis);
+        model
+    }
+
+    // the inverse of GeometryPipeline::run, where the relative depth of the landmarks is in the scale of the
+    // mean depth.
+    fn project(
+        camera: &PerspectiveCamera, pose: &Matrix4<f32>, model: &CanonicalModel, (h, w): (f32, f32),
+    ) -> Vec<Point3<f32>> {
+        let height_at_near = 2.0 * camera.near * f32::tan(camera.vertical_fov.to_radians() / 2.0);
+        let width_at_near = height_at_near * w / h;
+        let metric: Vec<_> = model.vertices.iter().map(|v| pose.transform_point(v)).collect();
+        let mean_depth = metric.iter().map(|p| -p.z).sum::<f32>() / metric.len() as f32;
+        metric
+            .iter()
+            .map(|p| {
+                let x = p.x * camera.near / -p.z;
+                let y = p.y * camera.near / -p.z;
+                Point3::new(
+                    (x / width_at_near + 0.5) * w,
+                    (0.5 - y / height_at_near) * h,
+                    -p.z * camera.near / mean_depth / width_at_near * w,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pbtxt() {
+        let mut model = model();
+        let src = "procrustes_landmark_basis { landmark_id: 4 weight: 0.070909939706326 }\n\
+                   procrustes_landmark_basis {\n  landmark_id: 6\n  weight: 0.032100144773722\n}\n";
+        model.set_weights_from_pbtxt(src).unwrap();
+        assert_eq!(model.weights.iter().filter(|w| **w != 0.0).count(), 2);
+        assert_eq!(
+            (model.weights[4], model.weights[6]),
+            (0.070909939706326, 0.032100144773722)
+        );
+        assert!(model
+            .set_weights_from_pbtxt("procrustes_landmark_basis { landmark_id: 468 weight: 1 }")
+            .is_none());
+        assert!(model.set_weights_from_pbtxt("").is_none());
+    }
+
+    #[test]
+    fn pose() {
+        let model = model();
+        let camera = PerspectiveCamera::default();
+        let size = (480.0, 640.0);
+        let rotation = Rotation3::from_euler_angles(-0.2, 0.3, 0.1);
+        let pose = Translation3::new(3.0, -2.0, -50.0).to_homogeneous() * rotation.to_homogeneous();
+
+        let landmarks = face_landmark::FaceLandmarks {
+            points: project(&camera, &pose, &model, size),
+            presence: 1.0,
+            valid: true,
+            detection: None,
+            transform: Affine2::identity(),
+            eyes: None,
+            debug: None,
+        };
+        let geometry = GeometryPipeline::new(model.clone(), camera).run(&landmarks, size);
+
+        let r = geometry.pose.fixed_view::<3, 3>(0, 0);
+        assert!((r - rotation.matrix()).amax() < 1e-4);
+        assert!((geometry.pose.fixed_view::<3, 1>(0, 3) - pose.fixed_view::<3, 1>(0, 3)).amax() < 1e-2);
+        for (p, q) in geometry.mesh.iter().zip(model.vertices.iter()) {
+            assert!((p - q).amax() < 1e-2);
+        }
+    }
+}
@@ -1,43 +1,197 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use crate::face_detector;
 use crate::image_util;
+use crate::iris_landmark;
+use crate::roi;
 use crate::tflite;
+use nalgebra::Point3;
 use std::*;
 
+#[derive(Clone, Debug)]
+pub struct LandmarkConfig {
+    pub min_presence: f32,
+    // of the model input, from the model metadata if None.
+    pub normalization: Option<image_util::Normalization>,
+    // of FaceLandmark::run.  run_with_roi samples the ROI instead.
+    pub resize: image_util::ResizeConfig,
+}
+
+impl Default for LandmarkConfig {
+    fn default() -> Self {
+        LandmarkConfig {
+            min_presence: 0.5,
+            normalization: None,
+            resize: image_util::ResizeConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceLandmarks {
+    // 468 points in pixels, z has the same scale as x.
+    pub points: Vec<Point3<f32>>,
+    // probability that a face is present, i.e. sigmoid of the model output.
+    pub presence: f32,
+    // presence >= LandmarkConfig::min_presence.
+    pub valid: bool,
+    // the detection the crop was derived from, if any.
+    pub detection: Option<face_detector::BBox>,
+    // from the model input to the source image.
+    pub transform: Affine2,
+    // [right, left], if the iris model is enabled.
+    pub eyes: Option<[iris_landmark::Eye; 2]>,
+    // intermediate results, if enabled by face_loop::FaceLoopConfig::debug.  not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub debug: Option<Box<DebugInfo>>,
+}
+
+// in pixels regardless of face_loop::FaceLoopConfig::space.  the detection is in FaceLandmarks::detection.
+#[derive(Clone, Debug)]
+pub struct DebugInfo {
+    pub roi: roi::Roi,
+    // from the source image to the model input and its inverse.
+    pub n_transform: Affine2,
+    pub i_transform: Affine2,
+    // the model input as is, but de-normalized.
+    pub input: image::RgbImage,
+    // the model output in pixels of the input, before i_transform.
+    pub raw_points: Vec<Point3<f32>>,
+}
+
+impl FaceLandmarks {
+    // the crop for the next frame, for results in coords::CoordinateSpace::Pixel.
+    pub fn roi(&self) -> roi::Roi {
+        roi::Roi::from_landmarks(&self.points)
+    }
+
+    // with the points, the detection and the eyes transformed, e.g. to another coordinate space.  debug is kept
+    // as is.
+    pub fn transformed(&self, transform: &Affine2) -> FaceLandmarks {
+        let points = |src: &[Point3<f32>]| src.iter().map(|p| transform.point3(p)).collect();
+        FaceLandmarks {
+            points: points(&self.points),
+            presence: self.presence,
+            valid: self.valid,
+            detection: self.detection.as_ref().map(|b| transform.bbox(b)),
+            transform: *transform * self.transform,
+            eyes: self.eyes.as_ref().map(|eyes| {
+                let eye = |e: &iris_landmark::Eye| iris_landmark::Eye {
+                    contour: points(&e.contour),
+                    iris: points(&e.iris),
+                };
+                [eye(&eyes[0]), eye(&eyes[1])]
+            }),
+            debug: self.debug.clone(),
+        }
+    }
+}
+
 pub struct FaceLandmark {
+    config: LandmarkConfig,
     size: usize,
+    normalization: image_util::Normalization,
     interp: tflite::Interpreter,
 }
 
 impl FaceLandmark {
     pub fn new() -> Self {
-        let interp = tflite::Interpreter::new(include_bytes!("../models/face_landmark.tflite"));
+        Self::with_config(LandmarkConfig::default())
+    }
+
+    pub fn with_config(config: LandmarkConfig) -> Self {
+        let model = include_bytes!("../models/face_landmark.tflite");
+        let interp = tflite::Interpreter::new(model);
+        let normalization = config
+            .normalization
+            .or_else(|| image_util::Normalization::from_model(model))
+            .unwrap_or_default();
 
         FaceLandmark {
+            config: config,
             size: 192,
+            normalization: normalization,
             interp: interp,
         }
     }
 
-    pub fn run<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(&self, image: &I) -> (Vec<(f32, f32, f32)>, f32) {
+    pub fn config(&self) -> &LandmarkConfig {
+        &self.config
+    }
+
+    // the one in effect, i.e. LandmarkConfig::normalization, the model metadata or the default.
+    pub fn normalization(&self) -> &image_util::Normalization {
+        &self.normalization
+    }
+
+    pub fn run<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(&self, image: &I) -> FaceLandmarks {
         let inputs = self.interp.inputs();
-        let transform = image_util::resize_keeping_aspect(inputs[0].data_mut(), self.size, image);
+        let t = image_util::resize(
+            inputs[0].data_mut(),
+            self.size,
+            image,
+            &self.config.resize,
+            &self.normalization,
+        );
+        self.invoke(&t)
+    }
 
-        self.interp.invoke();
+    // samples the ROI directly into the input tensor, without intermediate images.
+    pub fn run_with_roi<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, roi: &roi::Roi, border: roi::Border, interpolation: roi::Interpolation,
+    ) -> FaceLandmarks {
+        let inputs = self.interp.inputs();
+        let sampling = roi::Sampling {
+            border: border,
+            interpolation: interpolation,
+            normalization: self.normalization,
+        };
+        let transform = roi.sample_into(inputs[0].data_mut(), self.size, image, false, &sampling);
+        self.invoke(&transform)
+    }
+
+    // the model input of the last run, de-normalized to an image.
+    pub fn input_image(&self) -> image::RgbImage {
+        let inputs = self.interp.inputs();
+        let src: &[f32] = inputs[0].data_mut();
+        image::RgbImage::from_fn(self.size as u32, self.size as u32, |x, y| {
+            let i = 3 * (self.size * y as usize + x as usize);
+            let v = self.normalization.invert([src[i + 0], src[i + 1], src[i + 2]]);
+            image::Rgb([
+                f32::round(v[0]).clamp(0.0, 255.0) as u8,
+                f32::round(v[1]).clamp(0.0, 255.0) as u8,
+                f32::round(v[2]).clamp(0.0, 255.0) as u8,
+            ])
+        })
+    }
 
+    // the model output of the last run, in pixels of the model input.
+    pub fn raw_points(&self) -> Vec<Point3<f32>> {
         let outputs = self.interp.outputs();
         let landmarks: &[f32] = outputs[0].data();
-        let likelihood: &[f32] = outputs[1].data();
         assert_eq!(landmarks.len() % 3, 0);
+        (0..landmarks.len() / 3)
+            .map(|i| Point3::new(landmarks[3 * i + 0], landmarks[3 * i + 1], landmarks[3 * i + 2]))
+            .collect()
+    }
+
+    fn invoke(&self, transform: &Affine2) -> FaceLandmarks {
+        self.interp.invoke();
+
+        let outputs = self.interp.outputs();
+        let likelihood: &[f32] = outputs[1].data();
         assert_eq!(likelihood.len(), 1);
 
-        let mut dst = Vec::new();
-        for i in 0..landmarks.len() / 3 {
-            let x = transform.ax * landmarks[3 * i + 0] + transform.bx;
-            let y = transform.ay * landmarks[3 * i + 1] + transform.by;
-            let z = transform.ax * landmarks[3 * i + 2];
-            dst.push((x, y, z));
+        let presence = 1.0 / (1.0 + f32::exp(-likelihood[0]));
+        FaceLandmarks {
+            points: self.raw_points().iter().map(|p| transform.point3(p)).collect(),
+            presence: presence,
+            valid: presence >= self.config.min_presence,
+            detection: None,
+            transform: *transform,
+            eyes: None,
+            debug: None,
         }
-
-        (dst, likelihood[0])
     }
 }
@@ -1,62 +1,348 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::coords;
 use crate::face_detector;
 use crate::face_landmark;
-use imageproc::geometric_transformations;
-use nalgebra::{Matrix3, Vector2, Vector3};
-use std::convert::TryInto;
+use crate::iris_landmark;
+use crate::orientation;
+use crate::roi;
 use std::*;
 
+pub use crate::face_landmark::DebugInfo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // runs the detector on every frame.
+    StaticImage,
+    // crops the next frame from the landmarks of the previous one, and runs the detector only when less than
+    // max_num_faces are tracked (i.e. FaceLandmarks::valid) or every redetect_interval frames.
+    Video,
+}
+
+#[derive(Clone, Debug)]
+pub struct FaceLoopConfig {
+    pub mode: Mode,
+    // in frames, 0 for never.
+    pub redetect_interval: usize,
+    // IoU of the crops above which a detection is of a tracked face, and dropped in Mode::Video.  ref.
+    // min_similarity_threshold of AssociationNormRectCalculator.
+    pub track_iou: f32,
+    pub detector: face_detector::DetectorConfig,
+    pub landmark: face_landmark::LandmarkConfig,
+    // caps DetectorConfig::max_faces.
+    pub max_num_faces: usize,
+    // runs the iris model on both eyes.
+    pub iris: bool,
+    pub iris_config: iris_landmark::IrisConfig,
+    // of the output, overriding DetectorConfig::space.
+    pub space: coords::CoordinateSpace,
+    // the number of landmark interpreters run in parallel over the faces.
+    pub parallelism: usize,
+    // fills FaceLandmarks::debug.
+    pub debug: bool,
+    // of the input, which is made upright before the models.  the output is in the input regardless, except
+    // FaceLandmarks::debug, which is in the upright image.
+    pub orientation: orientation::Orientation,
+
+    // the crop of the landmark model from a detection, or from the landmarks of the previous frame in
+    // Mode::Video.  scale and shift are relative to the box size.
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub shift_x: f32,
+    pub shift_y: f32,
+    pub square: roi::Square,
+    // of BBox::key_points, from which the rotation is computed.
+    pub rotation_key_points: (usize, usize),
+    // of rotation_key_points.0 -> rotation_key_points.1 in the crop, in degrees.
+    pub target_angle: f32,
+    pub border: roi::Border,
+    pub interpolation: roi::Interpolation,
+}
+
+impl Default for FaceLoopConfig {
+    fn default() -> Self {
+        FaceLoopConfig {
+            mode: Mode::StaticImage,
+            redetect_interval: 0,
+            track_iou: 0.5,
+            detector: face_detector::DetectorConfig::default(),
+            landmark: face_landmark::LandmarkConfig::default(),
+            max_num_faces: 1,
+            iris: false,
+            iris_config: iris_landmark::IrisConfig::default(),
+            space: coords::CoordinateSpace::Pixel,
+            parallelism: 1,
+            debug: false,
+            orientation: orientation::Orientation::default(),
+            scale_x: 1.5,
+            scale_y: 1.5,
+            shift_x: 0.0,
+            shift_y: 0.0,
+            square: roi::Square::Long,
+            rotation_key_points: (0, 1),
+            target_angle: 0.0,
+            border: roi::Border::Constant(image::Rgb([127, 127, 127])),
+            interpolation: roi::Interpolation::Bilinear,
+        }
+    }
+}
+
+impl FaceLoopConfig {
+    // of the detector run by FaceLoop, which outputs in pixels of the upright image.
+    pub fn detector_config(&self) -> face_detector::DetectorConfig {
+        face_detector::DetectorConfig {
+            space: coords::CoordinateSpace::Pixel,
+            orientation: orientation::Orientation::default(),
+            max_faces: cmp::min(self.detector.max_faces, self.max_num_faces),
+            ..self.detector.clone()
+        }
+    }
+}
+
+struct Worker {
+    landmark: face_landmark::FaceLandmark,
+    iris: Option<iris_landmark::IrisLandmark>,
+}
+
 pub struct FaceLoop {
+    config: FaceLoopConfig,
     detector: face_detector::FaceDetector,
-    landmark: face_landmark::FaceLandmark,
+    workers: Vec<Worker>,
+    // ROIs in pixels of the upright image for the next frame.
+    tracks: Vec<roi::Roi>,
+    frames_since_detection: usize,
+    // of the tracks.
+    orientation: orientation::Orientation,
 }
 
 impl FaceLoop {
     pub fn new() -> Self {
+        Self::with_config(FaceLoopConfig::default())
+    }
+
+    pub fn with_config(config: FaceLoopConfig) -> Self {
         FaceLoop {
-            detector: face_detector::FaceDetector::new(),
-            landmark: face_landmark::FaceLandmark::new(),
+            detector: face_detector::FaceDetector::with_config(config.detector_config()),
+            workers: (0..cmp::max(config.parallelism, 1))
+                .map(|_| Worker {
+                    landmark: face_landmark::FaceLandmark::with_config(config.landmark.clone()),
+                    iris: if config.iris {
+                        Some(iris_landmark::IrisLandmark::with_config(config.iris_config.clone()))
+                    } else {
+                        None
+                    },
+                })
+                .collect(),
+            config: config,
+            tracks: Vec::new(),
+            frames_since_detection: 0,
+            orientation: orientation::Orientation::default(),
+        }
+    }
+
+    // in the order of DetectorConfig::order when detected.  in Mode::Video, the tracked faces in the order of the
+    // previous frame come first, followed by the new ones.
+    pub fn run<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I,
+    ) -> Vec<face_landmark::FaceLandmarks> {
+        self.run_oriented(image, self.config.orientation)
+    }
+
+    // overrides FaceLoopConfig::orientation, e.g. with orientation::Orientation::from_exif().  the tracked faces
+    // are forgotten when it changes.
+    pub fn run_oriented<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I, orientation: orientation::Orientation,
+    ) -> Vec<face_landmark::FaceLandmarks> {
+        if orientation != self.orientation {
+            self.reset();
+            self.orientation = orientation;
+        }
+        let results = if orientation.is_identity() {
+            self.run_upright(image)
+        } else {
+            let view = orientation::Oriented::new(image, orientation);
+            let transform = view.transform();
+            let results = self.run_upright(&view);
+            results.iter().map(|r| r.transformed(&transform)).collect()
+        };
+        results.into_iter().map(|r| self.to_space(r, image)).collect()
+    }
+
+    pub fn config(&self) -> &FaceLoopConfig {
+        &self.config
+    }
+
+    // forgets the faces tracked in Mode::Video.
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+        self.frames_since_detection = 0;
+    }
+
+    // in pixels.
+    fn run_upright<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I,
+    ) -> Vec<face_landmark::FaceLandmarks> {
+        match self.config.mode {
+            Mode::StaticImage => self.detect(image),
+            Mode::Video => {
+                let interval = self.config.redetect_interval;
+                let expired = interval > 0 && self.frames_since_detection >= interval;
+                let tracks = mem::take(&mut self.tracks);
+                let mut results = if expired {
+                    Vec::new()
+                } else {
+                    self.run_rois(image, &tracks)
+                };
+                // drops the lost faces, and detects new ones unless max_num_faces are tracked.  ref.
+                // prev_has_enough_faces in <https://github.com/google/mediapipe/blob/master/mediapipe/modules/face_landmark/face_landmark_front_cpu.pbtxt>.
+                results.retain(|r| r.valid);
+                if results.len() < self.config.max_num_faces {
+                    let c = &self.config;
+                    let rois: Vec<_> = results.iter().map(|r| self.track_roi(r)).collect();
+                    let bboxes: Vec<_> = self
+                        .detector
+                        .run(image)
+                        .into_iter()
+                        .filter(|bbox| {
+                            let roi = roi::Roi::from_detection(bbox, c.rotation_key_points, c.target_angle);
+                            let roi = self.transform_roi(&roi);
+                            // of the axis-aligned boxes, ignoring the rotations.
+                            rois.iter()
+                                .all(|r| face_detector::iou(r.center, r.size, roi.center, roi.size) <= c.track_iou)
+                        })
+                        .take(c.max_num_faces - results.len())
+                        .collect();
+                    results.extend(self.run_bboxes(image, bboxes));
+                    self.frames_since_detection = 0;
+                }
+                self.frames_since_detection += 1;
+                self.tracks = results.iter().filter(|r| r.valid).map(|r| self.track_roi(r)).collect();
+                results
+            }
         }
     }
 
-    pub fn run(&self, image: &image::RgbImage) -> (Vec<(f32, f32, f32)>, f32) {
+    // skips the detector, e.g. with FaceLandmarks::roi() of the previous frame.  the ROI is in pixels of the input,
+    // regardless of FaceLoopConfig::orientation, since it may be rotated as well.
+    pub fn run_with_roi<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &self, image: &I, roi: &roi::Roi,
+    ) -> face_landmark::FaceLandmarks {
+        let result = self.workers[0].run(image, roi, &self.config);
+        self.to_space(result, image)
+    }
+
+    // skips the detector, with bboxes in pixels of the upright image, e.g. from a detector of
+    // FaceLoopConfig::detector_config() run on orientation::Oriented::new(image, FaceLoopConfig::orientation).
+    pub fn run_with_detections<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I, bboxes: Vec<face_detector::BBox>,
+    ) -> Vec<face_landmark::FaceLandmarks> {
+        let orientation = self.config.orientation;
+        let results = if orientation.is_identity() {
+            self.run_bboxes(image, bboxes)
+        } else {
+            let view = orientation::Oriented::new(image, orientation);
+            let transform = view.transform();
+            let results = self.run_bboxes(&view, bboxes);
+            results.iter().map(|r| r.transformed(&transform)).collect()
+        };
+        results.into_iter().map(|r| self.to_space(r, image)).collect()
+    }
+
+    fn detect<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I,
+    ) -> Vec<face_landmark::FaceLandmarks> {
         let bboxes = self.detector.run(image);
-        let bbox = bboxes
-            .into_iter()
-            .max_by(|e0, e1| e0.score.partial_cmp(&e1.score).unwrap())
-            .unwrap();
-
-        let size = f32::round(1.5 * f32::max(bbox.size.0, bbox.size.1));
-        let dir_y = bbox.key_points[0].0 - bbox.key_points[1].0;
-        let dir_x = bbox.key_points[1].1 - bbox.key_points[0].1;
-        let len = f32::hypot(dir_y, dir_x);
-        let sin = dir_y / len;
-        let cos = dir_x / len;
-        let n_transform = Matrix3::new_translation(&(Vector2::new(size, size) / 2.0))
-            * Matrix3::new(cos, -sin, 0.0, sin, cos, 0.0, 0.0, 0.0, 1.0)
-            * Matrix3::new_translation(&-Vector2::new(bbox.center.1, bbox.center.0));
-        let i_transform = Matrix3::new_translation(&Vector2::new(bbox.center.1, bbox.center.0))
-            * Matrix3::new(cos, sin, 0.0, -sin, cos, 0.0, 0.0, 0.0, 1.0)
-            * Matrix3::new_translation(&(-Vector2::new(size, size) / 2.0));
-
-        let mut cropped = image::RgbImage::new(size as u32, size as u32);
-        geometric_transformations::warp_into(
-            image,
-            &geometric_transformations::Projection::from_matrix(n_transform.transpose().as_slice().try_into().unwrap())
-                .unwrap(),
-            geometric_transformations::Interpolation::Bilinear,
-            image::Rgb([127, 127, 127]),
-            &mut cropped,
-        );
-
-        let (landmarks, likelihood) = self.landmark.run(&cropped);
-
-        let mut dst = Vec::new();
-        for (x, y, z) in landmarks.iter() {
-            let v = i_transform * Vector3::new(*x, *y, 1.0);
-            dst.push((v[0], v[1], *z));
-        }
-
-        (dst, likelihood)
+        self.run_bboxes(image, bboxes)
+    }
+
+    fn run_bboxes<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I, bboxes: Vec<face_detector::BBox>,
+    ) -> Vec<face_landmark::FaceLandmarks> {
+        let c = &self.config;
+        let rois: Vec<_> = bboxes
+            .iter()
+            .map(|bbox| self.transform_roi(&roi::Roi::from_detection(bbox, c.rotation_key_points, c.target_angle)))
+            .collect();
+        let mut results = self.run_rois(image, &rois);
+        for (result, bbox) in results.iter_mut().zip(bboxes) {
+            result.detection = Some(bbox);
+        }
+        results
+    }
+
+    // the faces are distributed over the workers in round robin, and the results are in the order of the ROIs.
+    fn run_rois<I: image::GenericImageView<Pixel = image::Rgb<u8>> + Sync>(
+        &mut self, image: &I, rois: &[roi::Roi],
+    ) -> Vec<face_landmark::FaceLandmarks> {
+        let n = cmp::min(self.workers.len(), rois.len());
+        let config = &self.config;
+        if n <= 1 {
+            return rois.iter().map(|roi| self.workers[0].run(image, roi, config)).collect();
+        }
+
+        let workers = &mut self.workers[..n];
+        let mut results = vec![None; rois.len()];
+        thread::scope(|scope| {
+            let handles: Vec<_> = workers
+                .iter_mut()
+                .enumerate()
+                .map(|(w, worker)| {
+                    scope.spawn(move || {
+                        (w..rois.len())
+                            .step_by(n)
+                            .map(|i| (i, worker.run(image, &rois[i], config)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for handle in handles.into_iter() {
+                for (i, result) in handle.join().unwrap().into_iter() {
+                    results[i] = Some(result);
+                }
+            }
+        });
+        results.into_iter().map(|r| r.unwrap()).collect()
+    }
+
+    // the crop of the next frame.
+    fn track_roi(&self, result: &face_landmark::FaceLandmarks) -> roi::Roi {
+        self.transform_roi(&roi::Roi::from_landmarks_bounds(
+            &result.points,
+            self.config.target_angle,
+        ))
+    }
+
+    fn transform_roi(&self, roi: &roi::Roi) -> roi::Roi {
+        let c = &self.config;
+        roi.transform((c.scale_y, c.scale_x), (c.shift_y, c.shift_x), c.square)
+    }
+
+    fn to_space<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, result: face_landmark::FaceLandmarks, image: &I,
+    ) -> face_landmark::FaceLandmarks {
+        match self.config.space {
+            coords::CoordinateSpace::Pixel => result,
+            space => space.landmarks(&result, (image.height() as f32, image.width() as f32)),
+        }
+    }
+}
+
+impl Worker {
+    fn run<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, roi: &roi::Roi, config: &FaceLoopConfig,
+    ) -> face_landmark::FaceLandmarks {
+        let mut result = self
+            .landmark
+            .run_with_roi(image, roi, config.border, config.interpolation);
+        result.eyes = self.iris.as_ref().map(|iris| iris.run_with_face(image, &result.points));
+        if config.debug {
+            result.debug = Some(Box::new(DebugInfo {
+                roi: roi.clone(),
+                n_transform: result.transform.inverse(),
+                i_transform: result.transform,
+                input: self.landmark.input_image(),
+                raw_points: self.landmark.raw_points(),
+            }));
+        }
+        result
     }
 }
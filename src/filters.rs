@@ -0,0 +1,267 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+//
+// almost 1:1 translation of:
+//     mediapipe/util/filtering/low_pass_filter.cc
+//     mediapipe/util/filtering/one_euro_filter.cc
+//     mediapipe/util/filtering/relative_velocity_filter.cc
+//     mediapipe/calculators/util/landmarks_smoothing_calculator.cc
+//     Copyright 2019-2020 The MediaPipe Authors, under Apache License v2.0.
+use crate::face_landmark;
+use nalgebra::Point3;
+use std::collections::VecDeque;
+use std::*;
+
+#[derive(Clone, Debug)]
+pub enum FilterConfig {
+    OneEuro {
+        // initial guess of the frame rate, updated by the timestamps.
+        frequency: f32,
+        min_cutoff: f32,
+        beta: f32,
+        derivate_cutoff: f32,
+    },
+    RelativeVelocity {
+        window_size: usize,
+        velocity_scale: f32,
+    },
+}
+
+impl FilterConfig {
+    pub fn one_euro() -> Self {
+        FilterConfig::OneEuro {
+            frequency: 30.0,
+            min_cutoff: 1.0,
+            beta: 0.0,
+            derivate_cutoff: 1.0,
+        }
+    }
+
+    pub fn relative_velocity() -> Self {
+        FilterConfig::RelativeVelocity {
+            window_size: 5,
+            velocity_scale: 10.0,
+        }
+    }
+
+    fn build(&self) -> Filter {
+        match *self {
+            FilterConfig::OneEuro {
+                frequency,
+                min_cutoff,
+                beta,
+                derivate_cutoff,
+            } => Filter::OneEuro(OneEuroFilter::new(frequency, min_cutoff, beta, derivate_cutoff)),
+            FilterConfig::RelativeVelocity {
+                window_size,
+                velocity_scale,
+            } => Filter::RelativeVelocity(RelativeVelocityFilter::new(window_size, velocity_scale)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LowPassFilter {
+    initialized: bool,
+    raw_value: f32,
+    stored_value: f32,
+}
+
+impl LowPassFilter {
+    pub fn apply_with_alpha(&mut self, value: f32, alpha: f32) -> f32 {
+        let result = if self.initialized {
+            alpha * value + (1.0 - alpha) * self.stored_value
+        } else {
+            self.initialized = true;
+            value
+        };
+        self.raw_value = value;
+        self.stored_value = result;
+        result
+    }
+
+    pub fn last_raw_value(&self) -> Option<f32> {
+        if self.initialized {
+            Some(self.raw_value)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OneEuroFilter {
+    frequency: f32,
+    min_cutoff: f32,
+    beta: f32,
+    derivate_cutoff: f32,
+    x: LowPassFilter,
+    dx: LowPassFilter,
+    last_time: Option<time::Duration>,
+}
+
+impl OneEuroFilter {
+    pub fn new(frequency: f32, min_cutoff: f32, beta: f32, derivate_cutoff: f32) -> Self {
+        OneEuroFilter {
+            frequency: frequency,
+            min_cutoff: min_cutoff,
+            beta: beta,
+            derivate_cutoff: derivate_cutoff,
+            x: LowPassFilter::default(),
+            dx: LowPassFilter::default(),
+            last_time: None,
+        }
+    }
+
+    pub fn apply(&mut self, timestamp: time::Duration, value_scale: f32, value: f32) -> f32 {
+        if let Some(last_time) = self.last_time {
+            if timestamp <= last_time {
+                return value;
+            }
+            self.frequency = 1.0 / (timestamp - last_time).as_secs_f32();
+        }
+        self.last_time = Some(timestamp);
+
+        let dvalue = match self.x.last_raw_value() {
+            Some(last) => (value - last) * value_scale * self.frequency,
+            None => 0.0,
+        };
+        let edvalue = self.dx.apply_with_alpha(dvalue, self.alpha(self.derivate_cutoff));
+        let cutoff = self.min_cutoff + self.beta * f32::abs(edvalue);
+        self.x.apply_with_alpha(value, self.alpha(cutoff))
+    }
+
+    fn alpha(&self, cutoff: f32) -> f32 {
+        let te = 1.0 / self.frequency;
+        let tau = 1.0 / (2.0 * f32::consts::PI * cutoff);
+        1.0 / (1.0 + tau / te)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RelativeVelocityFilter {
+    max_window_size: usize,
+    velocity_scale: f32,
+    // (distance, duration), the latest first.
+    window: VecDeque<(f32, time::Duration)>,
+    low_pass: LowPassFilter,
+    last_value: f32,
+    last_timestamp: Option<time::Duration>,
+}
+
+impl RelativeVelocityFilter {
+    pub fn new(window_size: usize, velocity_scale: f32) -> Self {
+        RelativeVelocityFilter {
+            max_window_size: window_size,
+            velocity_scale: velocity_scale,
+            window: VecDeque::new(),
+            low_pass: LowPassFilter::default(),
+            last_value: 0.0,
+            last_timestamp: None,
+        }
+    }
+
+    pub fn apply(&mut self, timestamp: time::Duration, value_scale: f32, value: f32) -> f32 {
+        let alpha = match self.last_timestamp {
+            Some(last_timestamp) if timestamp <= last_timestamp => return value,
+            Some(last_timestamp) => {
+                let distance = value_scale * (value - self.last_value);
+                let duration = timestamp - last_timestamp;
+
+                let mut cumulative_distance = distance;
+                let mut cumulative_duration = duration;
+                let assumed_max_duration = time::Duration::from_secs(1) / 30;
+                let max_cumulative_duration = (1 + self.window.len() as u32) * assumed_max_duration;
+                for (d, t) in self.window.iter() {
+                    if cumulative_duration + *t > max_cumulative_duration {
+                        break;
+                    }
+                    cumulative_distance += d;
+                    cumulative_duration += *t;
+                }
+                let velocity = cumulative_distance / cumulative_duration.as_secs_f32();
+
+                self.window.push_front((distance, duration));
+                self.window.truncate(self.max_window_size);
+                1.0 - 1.0 / (1.0 + self.velocity_scale * f32::abs(velocity))
+            }
+            None => 1.0,
+        };
+
+        self.last_value = value;
+        self.last_timestamp = Some(timestamp);
+        self.low_pass.apply_with_alpha(value, alpha)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Filter {
+    OneEuro(OneEuroFilter),
+    RelativeVelocity(RelativeVelocityFilter),
+}
+
+impl Filter {
+    fn apply(&mut self, timestamp: time::Duration, value_scale: f32, value: f32) -> f32 {
+        match self {
+            Filter::OneEuro(f) => f.apply(timestamp, value_scale, value),
+            Filter::RelativeVelocity(f) => f.apply(timestamp, value_scale, value),
+        }
+    }
+}
+
+// filters x, y and z of each landmark independently.  one instance per face.
+pub struct LandmarksFilter {
+    config: FilterConfig,
+    filters: Vec<[Filter; 3]>,
+}
+
+impl LandmarksFilter {
+    pub fn new(config: FilterConfig) -> Self {
+        LandmarksFilter {
+            config: config,
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.clear();
+    }
+
+    pub fn apply(&mut self, landmarks: &mut face_landmark::FaceLandmarks, timestamp: time::Duration) {
+        self.apply_points(&mut landmarks.points, timestamp);
+    }
+
+    // the timestamp is of the frame, e.g. since the start of the capture.
+    pub fn apply_points(&mut self, points: &mut [Point3<f32>], timestamp: time::Duration) {
+        if self.filters.len() != points.len() {
+            self.filters = (0..points.len())
+                .map(|_| [self.config.build(), self.config.build(), self.config.build()])
+                .collect();
+        }
+
+        let scale = object_scale(points);
+        if scale <= 0.0 {
+            return;
+        }
+        let value_scale = 1.0 / scale;
+        for (p, f) in points.iter_mut().zip(self.filters.iter_mut()) {
+            p.x = f[0].apply(timestamp, value_scale, p.x);
+            p.y = f[1].apply(timestamp, value_scale, p.y);
+            p.z = f[2].apply(timestamp, value_scale, p.z);
+        }
+    }
+}
+
+// the mean of the width and the height of the bounding box.
+fn object_scale(points: &[Point3<f32>]) -> f32 {
+    let mut x0 = f32::INFINITY;
+    let mut y0 = f32::INFINITY;
+    let mut x1 = -f32::INFINITY;
+    let mut y1 = -f32::INFINITY;
+    for p in points.iter() {
+        x0 = f32::min(x0, p.x);
+        y0 = f32::min(y0, p.y);
+        x1 = f32::max(x1, p.x);
+        y1 = f32::max(y1, p.y);
+    }
+    ((x1 - x0) + (y1 - y0)) / 2.0
+}
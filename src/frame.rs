@@ -0,0 +1,261 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use std::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb,
+    Bgr,
+    Rgba,
+    Bgra,
+    Gray8,
+    // Y plane followed by interleaved U, V plane of half the resolution, with the same stride, which holds the
+    // U, V of an odd width.
+    Nv12,
+    // Y plane followed by U and V planes of half the resolution, with half the stride.
+    I420,
+    // Y0, U, Y1, V for each two pixels.
+    Yuyv,
+}
+
+impl PixelFormat {
+    // of the rows, or of the Y plane for planar formats.
+    pub fn min_stride(&self, width: usize) -> usize {
+        match *self {
+            PixelFormat::Rgb | PixelFormat::Bgr => 3 * width,
+            PixelFormat::Rgba | PixelFormat::Bgra => 4 * width,
+            PixelFormat::Gray8 | PixelFormat::I420 => width,
+            PixelFormat::Nv12 => 2 * width.div_ceil(2),
+            PixelFormat::Yuyv => 4 * width.div_ceil(2),
+        }
+    }
+
+    fn min_len(&self, width: usize, height: usize, stride: usize) -> usize {
+        let ch = height.div_ceil(2);
+        match *self {
+            PixelFormat::Nv12 => stride * height + stride * (ch - 1) + 2 * width.div_ceil(2),
+            PixelFormat::I420 => stride * height + 2 * stride.div_ceil(2) * ch,
+            _ => stride * (height - 1) + self.min_stride(width),
+        }
+    }
+}
+
+// a view of a raw camera frame, which is converted to RGB pixel by pixel while preprocessing, without copying
+// the whole frame.  YUV is BT.601 limited range, as most cameras.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    // in bytes.
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl<'a> Frame<'a> {
+    pub fn new(data: &'a [u8], width: u32, height: u32, format: PixelFormat) -> Self {
+        Self::with_stride(data, width, height, format.min_stride(width as usize), format)
+    }
+
+    pub fn with_stride(data: &'a [u8], width: u32, height: u32, stride: usize, format: PixelFormat) -> Self {
+        assert!(width > 0 && height > 0);
+        assert!(stride >= format.min_stride(width as usize));
+        assert!(data.len() >= format.min_len(width as usize, height as usize, stride));
+        Frame {
+            data: data,
+            width: width,
+            height: height,
+            stride: stride,
+            format: format,
+        }
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn to_rgb_image(&self) -> image::RgbImage {
+        image::RgbImage::from_fn(self.width, self.height, |x, y| {
+            image::GenericImageView::get_pixel(self, x, y)
+        })
+    }
+}
+
+impl<'a> image::GenericImageView for Frame<'a> {
+    type Pixel = image::Rgb<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // required by image 0.24, pinned in Cargo.toml.
+    fn bounds(&self) -> (u32, u32, u32, u32) {
+        (0, 0, self.width, self.height)
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> image::Rgb<u8> {
+        assert!(x < self.width && y < self.height);
+        let (x, y) = (x as usize, y as usize);
+        let row = &self.data[self.stride * y..];
+        match self.format {
+            PixelFormat::Rgb => image::Rgb([row[3 * x + 0], row[3 * x + 1], row[3 * x + 2]]),
+            PixelFormat::Bgr => image::Rgb([row[3 * x + 2], row[3 * x + 1], row[3 * x + 0]]),
+            PixelFormat::Rgba => image::Rgb([row[4 * x + 0], row[4 * x + 1], row[4 * x + 2]]),
+            PixelFormat::Bgra => image::Rgb([row[4 * x + 2], row[4 * x + 1], row[4 * x + 0]]),
+            PixelFormat::Gray8 => image::Rgb([row[x]; 3]),
+            PixelFormat::Nv12 => {
+                let uv = &self.data[self.stride * (self.height as usize + y / 2)..];
+                yuv_to_rgb(row[x], uv[2 * (x / 2) + 0], uv[2 * (x / 2) + 1])
+            }
+            PixelFormat::I420 => {
+                let c_stride = self.stride.div_ceil(2);
+                let c_size = c_stride * (self.height as usize).div_ceil(2);
+                let u = &self.data[self.stride * self.height as usize + c_stride * (y / 2)..];
+                let v = &u[c_size..];
+                yuv_to_rgb(row[x], u[x / 2], v[x / 2])
+            }
+            PixelFormat::Yuyv => {
+                let p = &row[4 * (x / 2)..];
+                yuv_to_rgb(p[2 * (x % 2)], p[1], p[3])
+            }
+        }
+    }
+}
+
+// ref. <https://en.wikipedia.org/wiki/YCbCr#ITU-R_BT.601_conversion>.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> image::Rgb<u8> {
+    let y = 298 * (y as i32 - 16);
+    let u = u as i32 - 128;
+    let v = v as i32 - 128;
+    let clamp = |c: i32| ((c + 128) >> 8).clamp(0, 255) as u8;
+    image::Rgb([clamp(y + 409 * v), clamp(y - 100 * u - 208 * v), clamp(y + 516 * u)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    const FORMATS: [PixelFormat; 8] = [
+        PixelFormat::Rgb,
+        PixelFormat::Bgr,
+        PixelFormat::Rgba,
+        PixelFormat::Bgra,
+        PixelFormat::Gray8,
+        PixelFormat::Nv12,
+        PixelFormat::I420,
+        PixelFormat::Yuyv,
+    ];
+
+    fn rgb(x: usize, y: usize) -> [u8; 3] {
+        [(10 * x) as u8, (20 * y) as u8, (x + 3 * y + 1) as u8]
+    }
+
+    fn luma(x: usize, y: usize) -> u8 {
+        (16 + 17 * x + 29 * y) as u8
+    }
+
+    // of the chroma sample (x, y).
+    fn chroma(x: usize, y: usize) -> (u8, u8) {
+        ((100 + 31 * x + 7 * y) as u8, (90 + 11 * x + 41 * y) as u8)
+    }
+
+    // the expected pixel (x, y).
+    fn expected(format: PixelFormat, x: usize, y: usize) -> image::Rgb<u8> {
+        match format {
+            PixelFormat::Rgb | PixelFormat::Bgr | PixelFormat::Rgba | PixelFormat::Bgra => image::Rgb(rgb(x, y)),
+            PixelFormat::Gray8 => image::Rgb([luma(x, y); 3]),
+            PixelFormat::Nv12 | PixelFormat::I420 => {
+                let (u, v) = chroma(x / 2, y / 2);
+                yuv_to_rgb(luma(x, y), u, v)
+            }
+            PixelFormat::Yuyv => {
+                let (u, v) = chroma(x / 2, y);
+                yuv_to_rgb(luma(x, y), u, v)
+            }
+        }
+    }
+
+    // of exactly the minimum length, with the padding filled by garbage.
+    fn encode(format: PixelFormat, w: usize, h: usize, stride: usize) -> Vec<u8> {
+        let mut data = vec![0xa5; format.min_len(w, h, stride)];
+        let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+        for y in 0..h {
+            let row = &mut data[stride * y..];
+            for x in 0..w {
+                let [r, g, b] = rgb(x, y);
+                match format {
+                    PixelFormat::Rgb => row[3 * x..3 * x + 3].copy_from_slice(&[r, g, b]),
+                    PixelFormat::Bgr => row[3 * x..3 * x + 3].copy_from_slice(&[b, g, r]),
+                    PixelFormat::Rgba => row[4 * x..4 * x + 4].copy_from_slice(&[r, g, b, 0]),
+                    PixelFormat::Bgra => row[4 * x..4 * x + 4].copy_from_slice(&[b, g, r, 0]),
+                    PixelFormat::Gray8 | PixelFormat::Nv12 | PixelFormat::I420 => row[x] = luma(x, y),
+                    PixelFormat::Yuyv => row[4 * (x / 2) + 2 * (x % 2)] = luma(x, y),
+                }
+            }
+            if format == PixelFormat::Yuyv {
+                for x in 0..cw {
+                    let (u, v) = chroma(x, y);
+                    row[4 * x + 1] = u;
+                    row[4 * x + 3] = v;
+                }
+            }
+        }
+        let c_stride = stride.div_ceil(2);
+        for y in 0..ch {
+            for x in 0..cw {
+                let (u, v) = chroma(x, y);
+                match format {
+                    PixelFormat::Nv12 => {
+                        let p = stride * (h + y) + 2 * x;
+                        data[p] = u;
+                        data[p + 1] = v;
+                    }
+                    PixelFormat::I420 => {
+                        let p = stride * h + c_stride * y + x;
+                        data[p] = u;
+                        data[p + c_stride * ch] = v;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn formats() {
+        for &format in FORMATS.iter() {
+            for &(w, h) in [(1, 1), (2, 2), (3, 3), (4, 2), (5, 3), (6, 5)].iter() {
+                for &padding in [0, 3].iter() {
+                    let stride = format.min_stride(w) + padding;
+                    let data = encode(format, w, h, stride);
+                    let frame = Frame::with_stride(&data, w as u32, h as u32, stride, format);
+                    assert_eq!(frame.dimensions(), (w as u32, h as u32));
+                    let image = frame.to_rgb_image();
+                    for (x, y, px) in image.enumerate_pixels() {
+                        let e = expected(format, x as usize, y as usize);
+                        assert_eq!(*px, e, "{:?} {}x{} stride {} at ({}, {})", format, w, h, stride, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn yuv() {
+        assert_eq!(yuv_to_rgb(16, 128, 128), image::Rgb([0, 0, 0]));
+        assert_eq!(yuv_to_rgb(235, 128, 128), image::Rgb([255, 255, 255]));
+        assert_eq!(yuv_to_rgb(81, 90, 240), image::Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn short_data() {
+        let data = vec![0; 3 * 3 + 3];
+        Frame::with_stride(&data, 3, 3, 3, PixelFormat::Nv12);
+    }
+}
@@ -0,0 +1,210 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::face_landmark;
+use crate::mesh;
+use nalgebra::{Matrix3, Matrix6, Point3, Rotation3, Vector3, Vector6};
+use std::*;
+
+// a generic face in mm, in the camera frame of a frontal face (x: right, y: down, z: forward).
+pub const REFERENCE_FACE: [(usize, [f32; 3]); 6] = [
+    (mesh::NOSE_TIP, [0.0, 0.0, 0.0]),
+    (mesh::CHIN, [0.0, 66.0, 13.0]),
+    (mesh::RIGHT_EYE_OUTER, [-45.0, -34.0, 27.0]),
+    (mesh::LEFT_EYE_OUTER, [45.0, -34.0, 27.0]),
+    (mesh::MOUTH_RIGHT, [-30.0, 30.0, 25.0]),
+    (mesh::MOUTH_LEFT, [30.0, 30.0, 25.0]),
+];
+
+#[derive(Clone, Debug)]
+pub struct Intrinsics {
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeadPose {
+    // in degrees, about the camera axes: yaw around y, pitch around x, roll around z.  all 0 when frontal.
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    // from the reference face to the camera frame.
+    pub rotation: Matrix3<f32>,
+    // in the units of the reference face.
+    pub translation: Vector3<f32>,
+    // RMS reprojection error in pixels.
+    pub residual: f32,
+}
+
+impl Intrinsics {
+    // focal length ~ image width, i.e. ~53 degrees horizontal FOV, and the principal point at the center.
+    // image_size is (height, width).
+    pub fn approximate(image_size: (f32, f32)) -> Self {
+        Intrinsics {
+            fx: image_size.1,
+            fy: image_size.1,
+            cx: image_size.1 / 2.0,
+            cy: image_size.0 / 2.0,
+        }
+    }
+}
+
+// the landmarks are in coords::CoordinateSpace::Pixel, and image_size is (height, width).
+pub fn estimate(
+    landmarks: &face_landmark::FaceLandmarks, image_size: (f32, f32), intrinsics: Option<&Intrinsics>,
+) -> HeadPose {
+    let reference: Vec<_> = REFERENCE_FACE.iter().map(|(i, p)| (*i, Point3::from(*p))).collect();
+    estimate_with_reference(landmarks, &reference, image_size, intrinsics)
+}
+
+// solvePnP by Levenberg-Marquardt, starting from a frontal pose.  the landmarks are in
+// coords::CoordinateSpace::Pixel.
+pub fn estimate_with_reference(
+    landmarks: &face_landmark::FaceLandmarks, reference: &[(usize, Point3<f32>)], image_size: (f32, f32),
+    intrinsics: Option<&Intrinsics>,
+) -> HeadPose {
+    let approximated;
+    let k = match intrinsics {
+        Some(k) => k,
+        None => {
+            approximated = Intrinsics::approximate(image_size);
+            &approximated
+        }
+    };
+    let objects: Vec<Vector3<f64>> = reference.iter().map(|(_, p)| p.coords.cast()).collect();
+    let images: Vec<(f64, f64)> = reference
+        .iter()
+        .map(|(i, _)| (landmarks.points[*i].x as f64, landmarks.points[*i].y as f64))
+        .collect();
+
+    let residuals = |params: &Vector6<f64>| -> Vec<f64> {
+        let r = Rotation3::new(Vector3::new(params[0], params[1], params[2]));
+        let t = Vector3::new(params[3], params[4], params[5]);
+        let mut dst = Vec::with_capacity(2 * objects.len());
+        for (o, p) in objects.iter().zip(images.iter()) {
+            let c = r * o + t;
+            dst.push(k.fx as f64 * c.x / c.z + k.cx as f64 - p.0);
+            dst.push(k.fy as f64 * c.y / c.z + k.cy as f64 - p.1);
+        }
+        dst
+    };
+
+    // the initial distance from the spread of the points.
+    let spread = |ps: &mut dyn Iterator<Item = (f64, f64)>| {
+        let ps: Vec<_> = ps.collect();
+        let n = ps.len() as f64;
+        let my = ps.iter().map(|p| p.0).sum::<f64>() / n;
+        let mx = ps.iter().map(|p| p.1).sum::<f64>() / n;
+        (ps.iter().map(|p| (p.0 - my).powi(2) + (p.1 - mx).powi(2)).sum::<f64>() / n).sqrt()
+    };
+    let z = k.fx as f64 * spread(&mut objects.iter().map(|o| (o.x, o.y)))
+        / f64::max(spread(&mut images.iter().cloned()), 1e-6);
+    let n = images.len() as f64;
+    let u = images.iter().map(|p| p.0).sum::<f64>() / n;
+    let v = images.iter().map(|p| p.1).sum::<f64>() / n;
+    let mut params = Vector6::new(
+        0.0,
+        0.0,
+        0.0,
+        (u - k.cx as f64) * z / k.fx as f64,
+        (v - k.cy as f64) * z / k.fy as f64,
+        z,
+    );
+
+    let cost = |e: &[f64]| e.iter().map(|e| e * e).sum::<f64>();
+    let mut lambda = 1e-3;
+    let mut e = residuals(&params);
+    for _ in 0..100 {
+        let mut jtj = Matrix6::zeros();
+        let mut jte = Vector6::zeros();
+        let mut jacobian = vec![Vector6::zeros(); e.len()];
+        for j in 0..6 {
+            let h = if j < 3 {
+                1e-6
+            } else {
+                1e-6 * f64::max(params[5].abs(), 1.0)
+            };
+            let mut p = params;
+            p[j] += h;
+            for (i, ei) in residuals(&p).iter().enumerate() {
+                jacobian[i][j] = (ei - e[i]) / h;
+            }
+        }
+        for (ji, ei) in jacobian.iter().zip(e.iter()) {
+            jtj += ji * ji.transpose();
+            jte += ji * *ei;
+        }
+
+        let mut a = jtj;
+        for j in 0..6 {
+            a[(j, j)] *= 1.0 + lambda;
+        }
+        let delta = match a.lu().solve(&-jte) {
+            Some(delta) => delta,
+            None => break,
+        };
+        let candidate = params + delta;
+        let e_candidate = residuals(&candidate);
+        if cost(&e_candidate) < cost(&e) {
+            params = candidate;
+            e = e_candidate;
+            lambda /= 10.0;
+            if delta.norm() < 1e-9 {
+                break;
+            }
+        } else {
+            lambda *= 10.0;
+        }
+    }
+
+    let rotation = Rotation3::new(Vector3::new(params[0], params[1], params[2])).cast::<f32>();
+    let (pitch, yaw, roll) = rotation.euler_angles();
+    HeadPose {
+        yaw: yaw.to_degrees(),
+        pitch: pitch.to_degrees(),
+        roll: roll.to_degrees(),
+        rotation: rotation.into_inner(),
+        translation: Vector3::new(params[3], params[4], params[5]).cast(),
+        residual: (cost(&e) / images.len() as f64).sqrt() as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::affine::Affine2;
+
+    #[test]
+    fn recovery() {
+        let size = (480.0, 640.0);
+        let k = Intrinsics::approximate(size);
+        for &(pitch, yaw, roll) in [(0.0, 0.0, 0.0), (10.0, -20.0, 5.0), (-15.0, 30.0, -10.0)].iter() {
+            let f = |d: f32| f32::to_radians(d);
+            let rotation = Rotation3::from_euler_angles(f(pitch), f(yaw), f(roll));
+            let translation = Vector3::new(20.0, -10.0, 600.0);
+            let mut points = vec![Point3::origin(); mesh::NUM_POINTS];
+            for (i, p) in REFERENCE_FACE.iter() {
+                let c = rotation * Vector3::from(*p) + translation;
+                points[*i] = Point3::new(k.fx * c.x / c.z + k.cx, k.fy * c.y / c.z + k.cy, 0.0);
+            }
+            let landmarks = face_landmark::FaceLandmarks {
+                points: points,
+                presence: 1.0,
+                valid: true,
+                detection: None,
+                transform: Affine2::identity(),
+                eyes: None,
+                debug: None,
+            };
+
+            let pose = estimate(&landmarks, size, None);
+            assert!((pose.pitch - pitch).abs() < 0.1, "{:?}", pose);
+            assert!((pose.yaw - yaw).abs() < 0.1, "{:?}", pose);
+            assert!((pose.roll - roll).abs() < 0.1, "{:?}", pose);
+            assert!((pose.rotation - rotation.matrix()).amax() < 1e-3, "{:?}", pose);
+            assert!((pose.translation - translation).amax() < 0.5, "{:?}", pose);
+            assert!(pose.residual < 1e-2, "{:?}", pose);
+        }
+    }
+}
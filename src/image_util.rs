@@ -1,61 +1,325 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use crate::metadata;
 use std::*;
 
-#[derive(Debug)]
-pub struct Transform {
-    pub ay: f32,
-    pub ax: f32,
-    pub by: f32,
-    pub bx: f32,
+// of the model input, from pixel values in [0, 255].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    // [0, 255] linearly to [min, max].
+    Range(f32, f32),
+    // (v - mean) / std per channel.
+    MeanStd([f32; 3], [f32; 3]),
 }
 
-pub fn resize_keeping_aspect<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
-    dst: &mut [f32], size: usize, src: &I,
-) -> Transform {
-    let src_size = (src.height() as usize, src.width() as usize);
-    let rect = if cmp::max(src_size.0, src_size.1) == size {
-        convert_with_padding(dst, size, src)
+// the models without metadata, which have always been fed [-0.5, 0.5].  MediaPipe uses Range(-1.0, 1.0).
+impl Default for Normalization {
+    fn default() -> Self {
+        Normalization::Range(-0.5, 0.5)
+    }
+}
+
+impl Normalization {
+    // from NormalizationOptions of the TFLite metadata, if any.
+    pub fn from_model(model: &[u8]) -> Option<Self> {
+        let (mean, std) = metadata::input_normalization(model)?;
+        // a single value is for all the channels.
+        let channels = |v: &[f32]| match v.len() {
+            1 => Some([v[0]; 3]),
+            3 => Some([v[0], v[1], v[2]]),
+            _ => None,
+        };
+        Some(Normalization::MeanStd(channels(&mean)?, channels(&std)?))
+    }
+
+    // as v * scale + bias per channel.
+    pub fn scale_bias(&self) -> ([f32; 3], [f32; 3]) {
+        match *self {
+            Normalization::Range(min, max) => {
+                let s = (max - min) / 255.0;
+                ([s; 3], [min; 3])
+            }
+            Normalization::MeanStd(mean, std) => (
+                [1.0 / std[0], 1.0 / std[1], 1.0 / std[2]],
+                [-mean[0] / std[0], -mean[1] / std[1], -mean[2] / std[2]],
+            ),
+        }
+    }
+
+    pub fn apply(&self, px: [f32; 3]) -> [f32; 3] {
+        let (scale, bias) = self.scale_bias();
+        [
+            px[0] * scale[0] + bias[0],
+            px[1] * scale[1] + bias[1],
+            px[2] * scale[2] + bias[2],
+        ]
+    }
+
+    // the inverse of apply.
+    pub fn invert(&self, v: [f32; 3]) -> [f32; 3] {
+        let (scale, bias) = self.scale_bias();
+        [
+            (v[0] - bias[0]) / scale[0],
+            (v[1] - bias[1]) / scale[1],
+            (v[2] - bias[2]) / scale[2],
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeMode {
+    // keeps the aspect ratio and pads the shorter side.
+    Letterbox,
+    // fills the input, with different scales in x and y.
+    Stretch,
+    // keeps the aspect ratio and crops the center square.
+    CenterCrop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResizeConfig {
+    pub mode: ResizeMode,
+    pub filter: image::imageops::FilterType,
+    // in [0, 255] before the normalization, for ResizeMode::Letterbox.
+    pub pad: [f32; 3],
+}
+
+impl Default for ResizeConfig {
+    fn default() -> Self {
+        ResizeConfig {
+            mode: ResizeMode::Letterbox,
+            filter: image::imageops::FilterType::Triangle,
+            pad: [127.5; 3],
+        }
+    }
+}
+
+// resizes the image into the size x size tensor, and returns the transform from the tensor to the image.
+pub fn resize<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+    dst: &mut [f32], size: usize, src: &I, config: &ResizeConfig, normalization: &Normalization,
+) -> Affine2 {
+    let (h, w) = (src.height() as usize, src.width() as usize);
+    // the source rectangle (y, x, h, w) and its size in the tensor.
+    let ((sy, sx, sh, sw), (th, tw)) = match config.mode {
+        ResizeMode::Letterbox => {
+            let scale = cmp::min(size * w, size * h);
+            ((0, 0, h, w), ((scale + w / 2) / w, (scale + h / 2) / h))
+        }
+        ResizeMode::Stretch => ((0, 0, h, w), (size, size)),
+        ResizeMode::CenterCrop => {
+            let s = cmp::min(h, w);
+            (((h - s) / 2, (w - s) / 2, s, s), (size, size))
+        }
+    };
+
+    let view = src.view(sx as u32, sy as u32, sw as u32, sh as u32);
+    let rect = if (th, tw) == (sh, sw) {
+        convert_with_padding(dst, size, &*view, normalization, config.pad)
     } else {
-        let scale = cmp::min(size * src_size.1, size * src_size.0);
-        let scaled_h = (scale + src_size.1 / 2) / src_size.1;
-        let scaled_w = (scale + src_size.0 / 2) / src_size.0;
-        let tmp = image::imageops::resize(
-            src,
-            scaled_w as u32,
-            scaled_h as u32,
-            image::imageops::FilterType::Triangle,
-        );
-        convert_with_padding(dst, size, &tmp)
+        let tmp = image::imageops::resize(&*view, tw as u32, th as u32, config.filter);
+        convert_rgb_with_padding(dst, size, &tmp, normalization, config.pad)
     };
 
-    let ay = src_size.0 as f32 / (rect.2 - rect.0) as f32;
-    let ax = src_size.1 as f32 / (rect.3 - rect.1) as f32;
-    Transform {
-        ay: ay,
-        ax: ax,
-        by: -ay * rect.0 as f32,
-        bx: -ax * rect.1 as f32,
+    let ay = sh as f32 / (rect.2 - rect.0) as f32;
+    let ax = sw as f32 / (rect.3 - rect.1) as f32;
+    Affine2::from_translation(sx as f32, sy as f32)
+        * Affine2::from_scale(ax, ay)
+        * Affine2::from_translation(-(rect.1 as f32), -(rect.0 as f32))
+}
+
+// the generic path.  the image is centered in the size x size tensor, and the rest is filled with pad.
+pub fn convert_with_padding<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+    dst: &mut [f32], size: usize, src: &I, normalization: &Normalization, pad: [f32; 3],
+) -> (usize, usize, usize, usize) {
+    let rect = padding_rect(size, src.width() as usize, src.height() as usize);
+    fill_padding(dst, size, rect, &normalization.apply(pad));
+    let (scale, bias) = normalization.scale_bias();
+    let (y0, x0, y1, x1) = rect;
+    for y in y0..y1 {
+        let row = &mut dst[(3 * size) * y + 3 * x0..(3 * size) * y + 3 * x1];
+        for (x, px) in row.chunks_exact_mut(3).enumerate() {
+            let src_px = src.get_pixel(x as u32, (y - y0) as u32);
+            for c in 0..3 {
+                px[c] = src_px[c] as f32 * scale[c] + bias[c];
+            }
+        }
     }
+    rect
 }
 
-fn convert_with_padding<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
-    dst: &mut [f32], size: usize, src: &I,
+// the fast path for contiguous rows.
+pub fn convert_rgb_with_padding(
+    dst: &mut [f32], size: usize, src: &image::RgbImage, normalization: &Normalization, pad: [f32; 3],
 ) -> (usize, usize, usize, usize) {
-    let y0 = (1 + size - src.height() as usize) / 2;
-    let x0 = (1 + size - src.width() as usize) / 2;
-    let y1 = y0 + src.height() as usize;
-    let x1 = x0 + src.width() as usize;
-    // XXX: optimize.
-    for y in 0..size {
-        for x in 0..size {
+    let rect = padding_rect(size, src.width() as usize, src.height() as usize);
+    fill_padding(dst, size, rect, &normalization.apply(pad));
+    let (scale, bias) = normalization.scale_bias();
+    let (y0, x0, y1, x1) = rect;
+    let src_rows = src.as_raw().chunks_exact(3 * (x1 - x0));
+    for (y, src_row) in (y0..y1).zip(src_rows) {
+        let row = &mut dst[(3 * size) * y + 3 * x0..(3 * size) * y + 3 * x1];
+        for (px, src_px) in row.chunks_exact_mut(3).zip(src_row.chunks_exact(3)) {
             for c in 0..3 {
-                dst[(3 * size) * y + 3 * x + c] = if y0 <= y && y < y1 && x0 <= x && x < x1 {
-                    src.get_pixel((x - x0) as u32, (y - y0) as u32)[c] as f32 / 255.0 - 0.5
+                px[c] = src_px[c] as f32 * scale[c] + bias[c];
+            }
+        }
+    }
+    rect
+}
+
+fn padding_rect(size: usize, width: usize, height: usize) -> (usize, usize, usize, usize) {
+    let y0 = (1 + size - height) / 2;
+    let x0 = (1 + size - width) / 2;
+    (y0, x0, y0 + height, x0 + width)
+}
+
+// with the normalized value.
+fn fill_padding(dst: &mut [f32], size: usize, (y0, x0, y1, x1): (usize, usize, usize, usize), value: &[f32; 3]) {
+    let fill = |dst: &mut [f32]| {
+        for px in dst.chunks_exact_mut(3) {
+            px.copy_from_slice(value);
+        }
+    };
+    let stride = 3 * size;
+    fill(&mut dst[..stride * y0]);
+    for y in y0..y1 {
+        fill(&mut dst[stride * y..stride * y + 3 * x0]);
+        fill(&mut dst[stride * y + 3 * x1..stride * (y + 1)]);
+    }
+    fill(&mut dst[stride * y1..stride * size]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point2;
+
+    fn assert_close(v: &[f32], e: &[f32]) {
+        assert!(
+            v.iter()
+                .zip(e.iter())
+                .all(|(v, e)| (v - e).abs() <= 1e-6 * f32::max(1.0, e.abs())),
+            "{:?} != {:?}",
+            v,
+            e
+        );
+    }
+
+    // 4 x 2, which is letterboxed into 4 x 4 without resampling.
+    fn image() -> image::RgbImage {
+        image::RgbImage::from_fn(4, 2, |x, y| image::Rgb([(85 * x) as u8, (255 * y) as u8, 51]))
+    }
+
+    fn check(normalization: &Normalization, expected: impl Fn([f32; 3]) -> [f32; 3]) {
+        let mut dst = vec![f32::NAN; 3 * 4 * 4];
+        let t = resize(&mut dst, 4, &image(), &ResizeConfig::default(), normalization);
+        for y in 0..4 {
+            for x in 0..4 {
+                let px = if y == 0 || y == 3 {
+                    [127.5; 3]
                 } else {
-                    0.0
+                    [(85 * x) as f32, (255 * (y - 1)) as f32, 51.0]
+                };
+                let i = 3 * (4 * y + x);
+                assert_close(&dst[i..i + 3], &expected(px));
+            }
+        }
+        // the pixel centers of the tensor to the ones of the image.
+        let p = t.point(&Point2::new(2.5, 1.5));
+        assert!((p - Point2::new(2.5, 0.5)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn range() {
+        check(&Normalization::Range(-1.0, 1.0), |px| {
+            [px[0] / 127.5 - 1.0, px[1] / 127.5 - 1.0, px[2] / 127.5 - 1.0]
+        });
+        check(&Normalization::Range(0.0, 1.0), |px| {
+            [px[0] / 255.0, px[1] / 255.0, px[2] / 255.0]
+        });
+        // the padding is the neutral value.
+        assert_close(&Normalization::Range(-1.0, 1.0).apply([127.5; 3]), &[0.0; 3]);
+        assert_close(
+            &Normalization::Range(0.0, 1.0).apply([0.0, 255.0, 51.0]),
+            &[0.0, 1.0, 0.2],
+        );
+    }
+
+    #[test]
+    fn mean_std() {
+        let normalization = Normalization::MeanStd([0.0, 127.5, 255.0], [1.0, 127.5, 51.0]);
+        check(&normalization, |px| {
+            [px[0], px[1] / 127.5 - 1.0, (px[2] - 255.0) / 51.0]
+        });
+        let v = normalization.apply([85.0, 255.0, 51.0]);
+        assert_close(&normalization.invert(v), &[85.0, 255.0, 51.0]);
+    }
+
+    #[test]
+    fn pad() {
+        let config = ResizeConfig {
+            pad: [0.0, 255.0, 51.0],
+            ..ResizeConfig::default()
+        };
+        let mut dst = vec![f32::NAN; 3 * 4 * 4];
+        resize(&mut dst, 4, &image(), &config, &Normalization::Range(0.0, 1.0));
+        for i in (0..4).chain(12..16) {
+            assert_close(&dst[3 * i..3 * i + 3], &[0.0, 1.0, 0.2]);
+        }
+    }
+
+    #[test]
+    fn stretch() {
+        // 8 x 2 into 4 x 4, i.e. x by 1/2 and y by 2.  pairs of columns are equal, thus exact with Nearest.
+        let image = image::RgbImage::from_fn(8, 2, |x, y| image::Rgb([(30 * (x / 2)) as u8, (255 * y) as u8, 51]));
+        let config = ResizeConfig {
+            mode: ResizeMode::Stretch,
+            filter: image::imageops::FilterType::Nearest,
+            ..ResizeConfig::default()
+        };
+        let mut dst = vec![f32::NAN; 3 * 4 * 4];
+        let t = resize(&mut dst, 4, &image, &config, &Normalization::Range(0.0, 255.0));
+        for y in 0..4 {
+            for x in 0..4 {
+                let i = 3 * (4 * y + x);
+                assert_close(&dst[i..i + 3], &[(30 * x) as f32, (255 * (y / 2)) as f32, 51.0]);
+            }
+        }
+        for &(p, e) in [
+            ((0.0, 0.0), (0.0, 0.0)),
+            ((4.0, 4.0), (8.0, 2.0)),
+            ((1.5, 2.5), (3.0, 1.25)),
+        ]
+        .iter()
+        {
+            let p = t.point(&Point2::new(p.0, p.1));
+            assert!((p - Point2::new(e.0, e.1)).norm() < 1e-6, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn center_crop() {
+        let image = |w, h| image::RgbImage::from_fn(w, h, |x, y| image::Rgb([(30 * x) as u8, (30 * y) as u8, 51]));
+        let config = ResizeConfig {
+            mode: ResizeMode::CenterCrop,
+            ..ResizeConfig::default()
+        };
+        // the offsets (sy, sx) of the 4 x 4 crops, without resampling.
+        for &(w, h, sy, sx) in [(8, 4, 0, 2), (4, 7, 1, 0)].iter() {
+            let mut dst = vec![f32::NAN; 3 * 4 * 4];
+            let t = resize(&mut dst, 4, &image(w, h), &config, &Normalization::Range(0.0, 255.0));
+            for y in 0..4 {
+                for x in 0..4 {
+                    let i = 3 * (4 * y + x);
+                    assert_close(&dst[i..i + 3], &[(30 * (x + sx)) as f32, (30 * (y + sy)) as f32, 51.0]);
                 }
             }
+            let p = t.point(&Point2::new(1.5, 2.5));
+            assert!(
+                (p - Point2::new(sx as f32 + 1.5, sy as f32 + 2.5)).norm() < 1e-6,
+                "{:?}",
+                p
+            );
         }
     }
-    (y0, x0, y1, x1)
 }
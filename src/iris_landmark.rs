@@ -0,0 +1,149 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use crate::image_util;
+use crate::mesh;
+use crate::roi;
+use crate::tflite;
+use nalgebra::Point3;
+use std::*;
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Eye {
+    // eyelids and eyebrow, 71 points.
+    pub contour: Vec<Point3<f32>>,
+    // the center followed by 4 points on the boundary.
+    pub iris: Vec<Point3<f32>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IrisConfig {
+    // of the model input, from the model metadata if None, or else [0, 1] as MediaPipe.
+    pub normalization: Option<image_util::Normalization>,
+    // of the eye crops.  MediaPipe replicates the border.
+    pub border: roi::Border,
+    pub interpolation: roi::Interpolation,
+    // of IrisLandmark::run.
+    pub resize: image_util::ResizeConfig,
+}
+
+impl Default for IrisConfig {
+    fn default() -> Self {
+        IrisConfig {
+            normalization: None,
+            border: roi::Border::Replicate,
+            interpolation: roi::Interpolation::Bilinear,
+            resize: image_util::ResizeConfig::default(),
+        }
+    }
+}
+
+pub struct IrisLandmark {
+    config: IrisConfig,
+    size: usize,
+    normalization: image_util::Normalization,
+    interp: tflite::Interpreter,
+}
+
+impl Default for IrisLandmark {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IrisLandmark {
+    pub fn new() -> Self {
+        Self::with_config(IrisConfig::default())
+    }
+
+    pub fn with_config(config: IrisConfig) -> Self {
+        let model = include_bytes!("../models/iris_landmark.tflite");
+        let interp = tflite::Interpreter::new(model);
+        // ref. zero_center: false of TfLiteConverterCalculator in
+        // <https://github.com/google/mediapipe/blob/master/mediapipe/modules/iris_landmark/iris_landmark_cpu.pbtxt>.
+        let normalization = config
+            .normalization
+            .or_else(|| image_util::Normalization::from_model(model))
+            .unwrap_or(image_util::Normalization::Range(0.0, 1.0));
+
+        IrisLandmark {
+            config: config,
+            size: 64,
+            normalization: normalization,
+            interp: interp,
+        }
+    }
+
+    pub fn config(&self) -> &IrisConfig {
+        &self.config
+    }
+
+    // the one in effect, i.e. IrisConfig::normalization, the model metadata or [0, 1].
+    pub fn normalization(&self) -> &image_util::Normalization {
+        &self.normalization
+    }
+
+    // the image is a crop around a right eye.
+    pub fn run<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(&self, image: &I) -> Eye {
+        let inputs = self.interp.inputs();
+        let t = image_util::resize(
+            inputs[0].data_mut(),
+            self.size,
+            image,
+            &self.config.resize,
+            &self.normalization,
+        );
+        self.invoke(&t)
+    }
+
+    // returns [right, left] eyes, in the same order as the refined mesh.
+    // ref. <https://github.com/google/mediapipe/blob/master/mediapipe/modules/iris_landmark/iris_landmark_left_and_right_cpu.pbtxt>.
+    pub fn run_with_face<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, landmarks: &[Point3<f32>],
+    ) -> [Eye; 2] {
+        let corner = |i: usize| (landmarks[i].y, landmarks[i].x);
+        let right = roi::Roi::from_points(corner(mesh::RIGHT_EYE_OUTER), corner(mesh::RIGHT_EYE_INNER), 2.3);
+        let left = roi::Roi::from_points(corner(mesh::LEFT_EYE_INNER), corner(mesh::LEFT_EYE_OUTER), 2.3);
+        // the model is trained on right eyes, so left eyes are flipped.
+        [
+            self.run_with_roi(image, &right, false),
+            self.run_with_roi(image, &left, true),
+        ]
+    }
+
+    fn run_with_roi<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, roi: &roi::Roi, flip: bool,
+    ) -> Eye {
+        let inputs = self.interp.inputs();
+        let sampling = roi::Sampling {
+            border: self.config.border,
+            interpolation: self.config.interpolation,
+            normalization: self.normalization,
+        };
+        let transform = roi.sample_into(inputs[0].data_mut(), self.size, image, flip, &sampling);
+        self.invoke(&transform)
+    }
+
+    fn invoke(&self, transform: &Affine2) -> Eye {
+        self.interp.invoke();
+
+        let outputs = self.interp.outputs();
+        let contour: &[f32] = outputs[0].data();
+        let iris: &[f32] = outputs[1].data();
+        assert_eq!(contour.len(), 3 * 71);
+        assert_eq!(iris.len(), 3 * 5);
+
+        let translate = |src: &[f32]| {
+            (0..src.len() / 3)
+                .map(|i| {
+                    let p = Point3::new(src[3 * i + 0], src[3 * i + 1], src[3 * i + 2]);
+                    transform.point3(&p)
+                })
+                .collect()
+        };
+        Eye {
+            contour: translate(contour),
+            iris: translate(iris),
+        }
+    }
+}
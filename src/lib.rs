@@ -1,7 +1,23 @@
 // (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+pub mod affine;
+pub mod coords;
+pub mod depth;
 pub mod face_detector;
+pub mod face_geometry;
 pub mod face_landmark;
 pub mod face_loop;
-mod image_util;
+pub mod filters;
+pub mod frame;
+pub mod head_pose;
+pub mod image_util;
+pub mod iris_landmark;
+pub mod mesh;
+mod metadata;
+pub mod orientation;
+pub mod pipeline;
+#[cfg(feature = "serde")]
+pub mod record;
+pub mod roi;
 mod ssd;
 mod tflite;
+pub mod tracker;
@@ -0,0 +1,268 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+//
+// index tables from:
+//     mediapipe/python/solutions/face_mesh_connections.py
+//     Copyright 2020 The MediaPipe Authors, under Apache License v2.0.
+//
+// "left" and "right" are from the subject's point of view, i.e. the right eye appears on the left side of a
+// non-mirrored image.  indices >= NUM_POINTS only exist in the refined mesh, where the iris points are appended.
+
+// TODO: triangle tessellation (mediapipe/modules/face_geometry/data/canonical_face_model.obj).
+
+pub const NUM_POINTS: usize = 468;
+pub const NUM_POINTS_WITH_IRISES: usize = 478;
+
+pub const FOREHEAD: usize = 10;
+pub const NOSE_TIP: usize = 1;
+pub const CHIN: usize = 152;
+pub const RIGHT_CHEEK: usize = 234;
+pub const LEFT_CHEEK: usize = 454;
+
+pub const RIGHT_EYE_OUTER: usize = 33;
+pub const RIGHT_EYE_INNER: usize = 133;
+pub const RIGHT_EYE_UPPER: usize = 159;
+pub const RIGHT_EYE_LOWER: usize = 145;
+pub const LEFT_EYE_OUTER: usize = 263;
+pub const LEFT_EYE_INNER: usize = 362;
+pub const LEFT_EYE_UPPER: usize = 386;
+pub const LEFT_EYE_LOWER: usize = 374;
+
+pub const MOUTH_RIGHT: usize = 61;
+pub const MOUTH_LEFT: usize = 291;
+pub const UPPER_LIP_OUTER: usize = 0;
+pub const UPPER_LIP_INNER: usize = 13;
+pub const LOWER_LIP_INNER: usize = 14;
+pub const LOWER_LIP_OUTER: usize = 17;
+
+pub const RIGHT_IRIS_CENTER: usize = 468;
+pub const LEFT_IRIS_CENTER: usize = 473;
+
+pub const LIPS: [(usize, usize); 40] = [
+    (61, 146),
+    (146, 91),
+    (91, 181),
+    (181, 84),
+    (84, 17),
+    (17, 314),
+    (314, 405),
+    (405, 321),
+    (321, 375),
+    (375, 291),
+    (61, 185),
+    (185, 40),
+    (40, 39),
+    (39, 37),
+    (37, 0),
+    (0, 267),
+    (267, 269),
+    (269, 270),
+    (270, 409),
+    (409, 291),
+    (78, 95),
+    (95, 88),
+    (88, 178),
+    (178, 87),
+    (87, 14),
+    (14, 317),
+    (317, 402),
+    (402, 318),
+    (318, 324),
+    (324, 308),
+    (78, 191),
+    (191, 80),
+    (80, 81),
+    (81, 82),
+    (82, 13),
+    (13, 312),
+    (312, 311),
+    (311, 310),
+    (310, 415),
+    (415, 308),
+];
+
+pub const LEFT_EYE: [(usize, usize); 16] = [
+    (263, 249),
+    (249, 390),
+    (390, 373),
+    (373, 374),
+    (374, 380),
+    (380, 381),
+    (381, 382),
+    (382, 362),
+    (263, 466),
+    (466, 388),
+    (388, 387),
+    (387, 386),
+    (386, 385),
+    (385, 384),
+    (384, 398),
+    (398, 362),
+];
+
+pub const LEFT_EYEBROW: [(usize, usize); 8] = [
+    (276, 283),
+    (283, 282),
+    (282, 295),
+    (295, 285),
+    (300, 293),
+    (293, 334),
+    (334, 296),
+    (296, 336),
+];
+
+pub const LEFT_IRIS: [(usize, usize); 4] = [(474, 475), (475, 476), (476, 477), (477, 474)];
+
+pub const RIGHT_EYE: [(usize, usize); 16] = [
+    (33, 7),
+    (7, 163),
+    (163, 144),
+    (144, 145),
+    (145, 153),
+    (153, 154),
+    (154, 155),
+    (155, 133),
+    (33, 246),
+    (246, 161),
+    (161, 160),
+    (160, 159),
+    (159, 158),
+    (158, 157),
+    (157, 173),
+    (173, 133),
+];
+
+pub const RIGHT_EYEBROW: [(usize, usize); 8] = [
+    (46, 53),
+    (53, 52),
+    (52, 65),
+    (65, 55),
+    (70, 63),
+    (63, 105),
+    (105, 66),
+    (66, 107),
+];
+
+pub const RIGHT_IRIS: [(usize, usize); 4] = [(469, 470), (470, 471), (471, 472), (472, 469)];
+
+pub const FACE_OVAL: [(usize, usize); 36] = [
+    (10, 338),
+    (338, 297),
+    (297, 332),
+    (332, 284),
+    (284, 251),
+    (251, 389),
+    (389, 356),
+    (356, 454),
+    (454, 323),
+    (323, 361),
+    (361, 288),
+    (288, 397),
+    (397, 365),
+    (365, 379),
+    (379, 378),
+    (378, 400),
+    (400, 377),
+    (377, 152),
+    (152, 148),
+    (148, 176),
+    (176, 149),
+    (149, 150),
+    (150, 136),
+    (136, 172),
+    (172, 58),
+    (58, 132),
+    (132, 93),
+    (93, 234),
+    (234, 127),
+    (127, 162),
+    (162, 21),
+    (21, 54),
+    (54, 103),
+    (103, 67),
+    (67, 109),
+    (109, 10),
+];
+
+// FACEMESH_CONTOURS, without the irises.
+pub const CONTOURS: [&[(usize, usize)]; 6] = [&LIPS, &LEFT_EYE, &LEFT_EYEBROW, &RIGHT_EYE, &RIGHT_EYEBROW, &FACE_OVAL];
+
+pub const IRISES: [&[(usize, usize)]; 2] = [&LEFT_IRIS, &RIGHT_IRIS];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // the degree of each vertex.
+    fn degrees(edges: &[(usize, usize)]) -> HashMap<usize, usize> {
+        let mut degrees = HashMap::new();
+        for &(i, j) in edges.iter() {
+            assert_ne!(i, j);
+            *degrees.entry(i).or_insert(0) += 1;
+            *degrees.entry(j).or_insert(0) += 1;
+        }
+        degrees
+    }
+
+    fn contains(edges: &[(usize, usize)], i: usize) -> bool {
+        edges.iter().any(|&(a, b)| a == i || b == i)
+    }
+
+    #[test]
+    fn indices() {
+        for edges in CONTOURS.iter() {
+            assert!(edges.iter().all(|&(i, j)| i < NUM_POINTS && j < NUM_POINTS));
+        }
+        for edges in IRISES.iter() {
+            let iris = |i: usize| NUM_POINTS <= i && i < NUM_POINTS_WITH_IRISES;
+            assert!(edges.iter().all(|&(i, j)| iris(i) && iris(j)));
+        }
+    }
+
+    #[test]
+    fn loops() {
+        // closed: every vertex is shared by two edges.
+        for edges in [&LIPS[..], &LEFT_EYE, &RIGHT_EYE, &FACE_OVAL, &LEFT_IRIS, &RIGHT_IRIS].iter() {
+            assert!(degrees(edges).values().all(|d| *d == 2));
+        }
+        // the outer and the inner lips.
+        assert_eq!(degrees(&LIPS).len(), LIPS.len());
+        // two chains, i.e. the upper and the lower lines, with four ends.
+        for edges in [&LEFT_EYEBROW, &RIGHT_EYEBROW].iter() {
+            let degrees = degrees(&edges[..]);
+            assert!(degrees.values().all(|d| *d <= 2));
+            assert_eq!(degrees.values().filter(|d| **d == 1).count(), 4);
+        }
+    }
+
+    #[test]
+    fn named_points() {
+        for i in [RIGHT_EYE_OUTER, RIGHT_EYE_INNER, RIGHT_EYE_UPPER, RIGHT_EYE_LOWER].iter() {
+            assert!(contains(&RIGHT_EYE, *i));
+        }
+        for i in [LEFT_EYE_OUTER, LEFT_EYE_INNER, LEFT_EYE_UPPER, LEFT_EYE_LOWER].iter() {
+            assert!(contains(&LEFT_EYE, *i));
+        }
+        for i in [
+            MOUTH_RIGHT,
+            MOUTH_LEFT,
+            UPPER_LIP_OUTER,
+            UPPER_LIP_INNER,
+            LOWER_LIP_INNER,
+            LOWER_LIP_OUTER,
+        ]
+        .iter()
+        {
+            assert!(contains(&LIPS, *i));
+        }
+        for i in [FOREHEAD, CHIN, RIGHT_CHEEK, LEFT_CHEEK].iter() {
+            assert!(contains(&FACE_OVAL, *i));
+        }
+        assert!(NOSE_TIP < NUM_POINTS);
+        // each center is followed by its contour.
+        for (center, edges) in [(RIGHT_IRIS_CENTER, &RIGHT_IRIS), (LEFT_IRIS_CENTER, &LEFT_IRIS)].iter() {
+            assert!(!contains(&edges[..], *center));
+            assert!((center + 1..center + 5).all(|i| contains(&edges[..], i)));
+        }
+    }
+}
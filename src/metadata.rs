@@ -0,0 +1,346 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+// a minimal reader of the TFLite metadata, just enough for the input normalization.
+// ref. <https://github.com/tensorflow/tensorflow/blob/master/tensorflow/lite/schema/schema.fbs>.
+// ref. <https://github.com/tensorflow/tflite-support/blob/master/tensorflow_lite_support/metadata/metadata_schema.fbs>.
+use std::convert::TryInto;
+use std::*;
+
+// returns (mean, std) of NormalizationOptions of the first input tensor, if any.
+pub fn input_normalization(model: &[u8]) -> Option<(Vec<f32>, Vec<f32>)> {
+    let root = Table::root(model)?;
+    let buffers = root.tables(4)?;
+    let metadata = root.tables(6)?;
+    let entry = metadata
+        .into_iter()
+        .find(|m| m.string(0) == Some(&b"TFLITE_METADATA"[..]))?;
+    let buffer = buffers.get(entry.u32(1)? as usize)?;
+    let data = match buffer.bytes(0) {
+        Some(data) if !data.is_empty() => data,
+        // stored outside of the flatbuffer, after the model.
+        _ => {
+            let offset = buffer.u64(1)? as usize;
+            model.get(offset..offset.checked_add(buffer.u64(2)? as usize)?)?
+        }
+    };
+
+    let model_metadata = Table::root(data)?;
+    let subgraph = model_metadata.tables(3)?.into_iter().next()?;
+    let tensor = subgraph.tables(2)?.into_iter().next()?;
+    for unit in tensor.tables(4)?.into_iter() {
+        // ProcessUnitOptions::NormalizationOptions.
+        if unit.u8(0) == Some(1) {
+            let options = unit.table(1)?;
+            return Some((options.f32s(0)?, options.f32s(1)?));
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy)]
+struct Table<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Table<'a> {
+    fn root(buf: &'a [u8]) -> Option<Self> {
+        Self::at(buf, read_u32(buf, 0)? as usize)
+    }
+
+    fn at(buf: &'a [u8], pos: usize) -> Option<Self> {
+        // checks the vtable once, so that fields can be read without overflows.
+        let vtable = pos as i64 - read_u32(buf, pos)? as i32 as i64;
+        if vtable < 0 {
+            return None;
+        }
+        read_u16(buf, vtable as usize)?;
+        Some(Table { buf: buf, pos: pos })
+    }
+
+    // the absolute position of the field, if present.
+    fn field(&self, i: usize) -> Option<usize> {
+        let vtable = (self.pos as i64 - read_u32(self.buf, self.pos)? as i32 as i64) as usize;
+        if 4 + 2 * i >= read_u16(self.buf, vtable)? as usize {
+            return None;
+        }
+        match read_u16(self.buf, vtable + 4 + 2 * i)? {
+            0 => None,
+            offset => Some(self.pos + offset as usize),
+        }
+    }
+
+    fn u8(&self, i: usize) -> Option<u8> {
+        self.buf.get(self.field(i)?).copied()
+    }
+
+    fn u32(&self, i: usize) -> Option<u32> {
+        read_u32(self.buf, self.field(i)?)
+    }
+
+    fn u64(&self, i: usize) -> Option<u64> {
+        let p = self.field(i)?;
+        Some(u64::from_le_bytes(self.buf.get(p..p + 8)?.try_into().ok()?))
+    }
+
+    fn indirect(&self, i: usize) -> Option<usize> {
+        let p = self.field(i)?;
+        p.checked_add(read_u32(self.buf, p)? as usize)
+    }
+
+    fn table(&self, i: usize) -> Option<Table<'a>> {
+        Table::at(self.buf, self.indirect(i)?)
+    }
+
+    // of a vector or a string.
+    fn bytes(&self, i: usize) -> Option<&'a [u8]> {
+        let p = self.indirect(i)?;
+        let n = read_u32(self.buf, p)? as usize;
+        self.buf.get(p + 4..(p + 4).checked_add(n)?)
+    }
+
+    fn string(&self, i: usize) -> Option<&'a [u8]> {
+        self.bytes(i)
+    }
+
+    fn f32s(&self, i: usize) -> Option<Vec<f32>> {
+        let p = self.indirect(i)?;
+        let n = read_u32(self.buf, p)? as usize;
+        let data = self.buf.get(p + 4..(p + 4).checked_add(n.checked_mul(4)?)?)?;
+        Some(
+            data.chunks_exact(4)
+                .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    fn tables(&self, i: usize) -> Option<Vec<Table<'a>>> {
+        let p = self.indirect(i)?;
+        let n = read_u32(self.buf, p)? as usize;
+        (0..n)
+            .map(|j| {
+                let e = p + 4 + 4 * j;
+                Table::at(self.buf, e.checked_add(read_u32(self.buf, e)? as usize)?)
+            })
+            .collect()
+    }
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(pos..pos.checked_add(2)?)?.try_into().ok()?))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(buf.get(pos..pos.checked_add(4)?)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_util::Normalization;
+
+    // a flatbuffer, whose objects follow the referrers.
+    enum Node {
+        Table(Vec<Field>),
+        Bytes(Vec<u8>),
+        F32s(Vec<f32>),
+        Tables(Vec<Node>),
+    }
+
+    enum Field {
+        Absent,
+        U8(u8),
+        U32(u32),
+        U64(u64),
+        Ref(Node),
+    }
+
+    fn string(s: &str) -> Field {
+        Field::Ref(Node::Bytes(s.as_bytes().to_vec()))
+    }
+
+    fn build(root: &Node) -> Vec<u8> {
+        let mut buf = vec![0; 4];
+        let pos = write(&mut buf, root);
+        buf[0..4].copy_from_slice(&(pos as u32).to_le_bytes());
+        buf
+    }
+
+    // returns the position of the node.
+    fn write(buf: &mut Vec<u8>, node: &Node) -> usize {
+        match node {
+            Node::Table(fields) => {
+                // the vtable, followed by the table whose fields are in 8 bytes each.
+                let vtable = buf.len();
+                let offsets: Vec<u16> = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| match f {
+                        Field::Absent => 0,
+                        _ => 4 + 8 * i as u16,
+                    })
+                    .collect();
+                buf.extend_from_slice(&(4 + 2 * fields.len() as u16).to_le_bytes());
+                buf.extend_from_slice(&(4 + 8 * fields.len() as u16).to_le_bytes());
+                for o in offsets.iter() {
+                    buf.extend_from_slice(&o.to_le_bytes());
+                }
+                let pos = buf.len();
+                buf.extend_from_slice(&((pos - vtable) as u32).to_le_bytes());
+                for f in fields.iter() {
+                    let mut slot = [0; 8];
+                    match f {
+                        Field::U8(v) => slot[0] = *v,
+                        Field::U32(v) => slot[..4].copy_from_slice(&v.to_le_bytes()),
+                        Field::U64(v) => slot.copy_from_slice(&v.to_le_bytes()),
+                        _ => (),
+                    }
+                    buf.extend_from_slice(&slot);
+                }
+                for (i, f) in fields.iter().enumerate() {
+                    if let Field::Ref(child) = f {
+                        let child = write(buf, child);
+                        patch(buf, pos + 4 + 8 * i, child);
+                    }
+                }
+                pos
+            }
+            Node::Bytes(bytes) => {
+                let pos = buf.len();
+                buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                buf.extend_from_slice(bytes);
+                pos
+            }
+            Node::F32s(v) => {
+                let pos = buf.len();
+                buf.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                for e in v.iter() {
+                    buf.extend_from_slice(&e.to_le_bytes());
+                }
+                pos
+            }
+            Node::Tables(tables) => {
+                let pos = buf.len();
+                buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+                buf.extend(iter::repeat(0).take(4 * tables.len()));
+                for (i, t) in tables.iter().enumerate() {
+                    let child = write(buf, t);
+                    patch(buf, pos + 4 + 4 * i, child);
+                }
+                pos
+            }
+        }
+    }
+
+    fn patch(buf: &mut [u8], at: usize, target: usize) {
+        buf[at..at + 4].copy_from_slice(&((target - at) as u32).to_le_bytes());
+    }
+
+    fn model_metadata(mean: &[f32], std: &[f32]) -> Vec<u8> {
+        let options = Node::Table(vec![
+            Field::Ref(Node::F32s(mean.to_vec())),
+            Field::Ref(Node::F32s(std.to_vec())),
+        ]);
+        let unit = Node::Table(vec![Field::U8(1), Field::Ref(options)]);
+        let tensor = Node::Table(vec![
+            Field::Absent,
+            Field::Absent,
+            Field::Absent,
+            Field::Absent,
+            Field::Ref(Node::Tables(vec![unit])),
+        ]);
+        let subgraph = Node::Table(vec![
+            Field::Absent,
+            Field::Absent,
+            Field::Ref(Node::Tables(vec![tensor])),
+        ]);
+        build(&Node::Table(vec![
+            Field::Absent,
+            Field::Absent,
+            Field::Absent,
+            Field::Ref(Node::Tables(vec![subgraph])),
+        ]))
+    }
+
+    // with the metadata in buffer 1, after the version of buffer 0.
+    fn model(buffer: Node) -> Vec<u8> {
+        let metadata = vec![
+            Node::Table(vec![string("min_runtime_version"), Field::U32(0)]),
+            Node::Table(vec![string("TFLITE_METADATA"), Field::U32(1)]),
+        ];
+        build(&Node::Table(vec![
+            Field::Absent,
+            Field::Absent,
+            Field::Absent,
+            Field::Absent,
+            Field::Ref(Node::Tables(vec![
+                Node::Table(vec![Field::Ref(Node::Bytes(b"1.5.0".to_vec()))]),
+                buffer,
+            ])),
+            Field::Absent,
+            Field::Ref(Node::Tables(metadata)),
+        ]))
+    }
+
+    #[test]
+    fn inline() {
+        let metadata = model_metadata(&[127.5, 127.0, 126.5], &[128.0, 127.5, 127.0]);
+        let model = model(Node::Table(vec![Field::Ref(Node::Bytes(metadata))]));
+        assert_eq!(
+            input_normalization(&model),
+            Some((vec![127.5, 127.0, 126.5], vec![128.0, 127.5, 127.0]))
+        );
+        assert_eq!(
+            Normalization::from_model(&model),
+            Some(Normalization::MeanStd([127.5, 127.0, 126.5], [128.0, 127.5, 127.0]))
+        );
+    }
+
+    #[test]
+    fn broadcast() {
+        let metadata = model_metadata(&[127.5], &[127.5]);
+        let model = model(Node::Table(vec![Field::Ref(Node::Bytes(metadata))]));
+        assert_eq!(
+            Normalization::from_model(&model),
+            Some(Normalization::MeanStd([127.5; 3], [127.5; 3]))
+        );
+    }
+
+    #[test]
+    fn outside() {
+        let metadata = model_metadata(&[0.0], &[255.0]);
+        // the offset is not known until the flatbuffer is built, but its size is fixed.
+        let size = model(Node::Table(vec![Field::Absent, Field::U64(0), Field::U64(0)])).len();
+        let mut data = model(Node::Table(vec![
+            Field::Absent,
+            Field::U64(size as u64),
+            Field::U64(metadata.len() as u64),
+        ]));
+        assert_eq!(data.len(), size);
+        data.extend_from_slice(&metadata);
+        assert_eq!(
+            Normalization::from_model(&data),
+            Some(Normalization::MeanStd([0.0; 3], [255.0; 3]))
+        );
+        // truncated.
+        assert_eq!(input_normalization(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let metadata = model_metadata(&[127.5], &[127.5]);
+        let model = model(Node::Table(vec![Field::Ref(Node::Bytes(metadata))]));
+        for n in 0..model.len() {
+            assert_eq!(input_normalization(&model[..n]), None);
+        }
+        for i in 0..model.len() {
+            let mut data = model.clone();
+            data[i] ^= 0xff;
+            input_normalization(&data);
+        }
+        // the bundled models have no metadata.
+        assert_eq!(
+            input_normalization(include_bytes!("../models/face_landmark.tflite")),
+            None
+        );
+    }
+}
@@ -0,0 +1,255 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use nalgebra::{Matrix2, Vector2};
+use std::*;
+
+// clockwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+// how to make the image upright: rotated, and then mirrored horizontally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub mirror: bool,
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation {
+            rotation: Rotation::None,
+            mirror: false,
+        }
+    }
+}
+
+impl Orientation {
+    pub fn new(rotation: Rotation, mirror: bool) -> Self {
+        Orientation {
+            rotation: rotation,
+            mirror: mirror,
+        }
+    }
+
+    // ref. <https://www.exif.org/Exif2-2.PDF>, Orientation (0x0112).
+    pub fn from_exif_value(value: u16) -> Option<Self> {
+        let (rotation, mirror) = match value {
+            1 => (Rotation::None, false),
+            2 => (Rotation::None, true),
+            3 => (Rotation::Cw180, false),
+            4 => (Rotation::Cw180, true),
+            5 => (Rotation::Cw90, true),
+            6 => (Rotation::Cw90, false),
+            7 => (Rotation::Cw270, true),
+            8 => (Rotation::Cw270, false),
+            _ => return None,
+        };
+        Some(Orientation::new(rotation, mirror))
+    }
+
+    // from a JPEG file or a TIFF-structured EXIF block, if the tag exists.
+    pub fn from_exif(data: &[u8]) -> Option<Self> {
+        Self::from_exif_value(exif_orientation(data)?)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Orientation::default()
+    }
+
+    // the upright size of an image of (height, width).
+    pub fn size(&self, (h, w): (u32, u32)) -> (u32, u32) {
+        match self.rotation {
+            Rotation::None | Rotation::Cw180 => (h, w),
+            Rotation::Cw90 | Rotation::Cw270 => (w, h),
+        }
+    }
+
+    // from the upright image to the source one of (height, width), in continuous coordinates, i.e. pixel
+    // (i, j) is centered at (i + 0.5, j + 0.5).
+    pub fn transform(&self, image_size: (f32, f32)) -> Affine2 {
+        let (h, w) = image_size;
+        let upright_w = match self.rotation {
+            Rotation::None | Rotation::Cw180 => w,
+            Rotation::Cw90 | Rotation::Cw270 => h,
+        };
+        let m = if self.mirror {
+            Affine2::from_translation(upright_w, 0.0) * Affine2::from_scale(-1.0, 1.0)
+        } else {
+            Affine2::identity()
+        };
+        let r = match self.rotation {
+            Rotation::None => Affine2::identity(),
+            Rotation::Cw90 => Affine2::new(Matrix2::new(0.0, 1.0, -1.0, 0.0), Vector2::new(0.0, h)),
+            Rotation::Cw180 => Affine2::from_translation(w, h) * Affine2::from_scale(-1.0, -1.0),
+            Rotation::Cw270 => Affine2::new(Matrix2::new(0.0, -1.0, 1.0, 0.0), Vector2::new(w, 0.0)),
+        };
+        r * m
+    }
+}
+
+// decodes the image with the orientation of its EXIF, if any.
+pub fn load_from_memory(data: &[u8]) -> image::ImageResult<(image::RgbImage, Orientation)> {
+    let image = image::load_from_memory(data)?.to_rgb8();
+    Ok((image, Orientation::from_exif(data).unwrap_or_default()))
+}
+
+// a view of the upright image, which is rotated pixel by pixel while preprocessing.
+#[derive(Clone, Copy, Debug)]
+pub struct Oriented<'a, I> {
+    image: &'a I,
+    orientation: Orientation,
+}
+
+impl<'a, I: image::GenericImageView<Pixel = image::Rgb<u8>>> Oriented<'a, I> {
+    pub fn new(image: &'a I, orientation: Orientation) -> Self {
+        Oriented {
+            image: image,
+            orientation: orientation,
+        }
+    }
+
+    // from the upright image to the source one.
+    pub fn transform(&self) -> Affine2 {
+        self.orientation
+            .transform((self.image.height() as f32, self.image.width() as f32))
+    }
+}
+
+impl<'a, I: image::GenericImageView<Pixel = image::Rgb<u8>>> image::GenericImageView for Oriented<'a, I> {
+    type Pixel = image::Rgb<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        let (h, w) = self.orientation.size((self.image.height(), self.image.width()));
+        (w, h)
+    }
+
+    // required by image 0.24, pinned in Cargo.toml.
+    fn bounds(&self) -> (u32, u32, u32, u32) {
+        let (w, h) = self.dimensions();
+        (0, 0, w, h)
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> image::Rgb<u8> {
+        let (w, h) = (self.image.width(), self.image.height());
+        let x = if self.orientation.mirror {
+            self.width() - 1 - x
+        } else {
+            x
+        };
+        match self.orientation.rotation {
+            Rotation::None => self.image.get_pixel(x, y),
+            Rotation::Cw90 => self.image.get_pixel(y, h - 1 - x),
+            Rotation::Cw180 => self.image.get_pixel(w - 1 - x, h - 1 - y),
+            Rotation::Cw270 => self.image.get_pixel(w - 1 - y, x),
+        }
+    }
+}
+
+// the value of the Orientation tag in IFD0.
+fn exif_orientation(data: &[u8]) -> Option<u16> {
+    if data.starts_with(b"\xff\xd8") {
+        // JPEG segments up to the image data.
+        let mut pos = 2;
+        while data.get(pos) == Some(&0xff) {
+            let marker = *data.get(pos + 1)?;
+            if marker == 0xda {
+                break;
+            }
+            let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+            let segment = data.get(pos + 4..pos + 2 + len)?;
+            if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+                return tiff_orientation(&segment[6..]);
+            }
+            pos += 2 + len;
+        }
+        None
+    } else {
+        tiff_orientation(data.strip_prefix(b"Exif\0\0").unwrap_or(data))
+    }
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(0..4)? {
+        b"II*\0" => false,
+        b"MM\0*" => true,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| {
+        let b = [*tiff.get(pos)?, *tiff.get(pos + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    };
+    let u32_at = |pos: usize| {
+        let (hi, lo) = (u16_at(pos)? as u32, u16_at(pos + 2)? as u32);
+        Some(if big_endian { (hi << 16) | lo } else { (lo << 16) | hi })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    for i in 0..u16_at(ifd)? as usize {
+        let entry = ifd + 2 + 12 * i;
+        // SHORT, whose value is in the first bytes of the value field.
+        if u16_at(entry)? == 0x0112 && u16_at(entry + 2)? == 3 {
+            return u16_at(entry + 8);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+    use nalgebra::Point2;
+
+    #[test]
+    fn transform() {
+        let image = image::RgbImage::from_fn(3, 2, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        for value in 1..=8 {
+            let orientation = Orientation::from_exif_value(value).unwrap();
+            let view = Oriented::new(&image, orientation);
+            let (h, w) = orientation.size((2, 3));
+            assert_eq!(view.dimensions(), (w, h));
+            let t = view.transform();
+            for y in 0..h {
+                for x in 0..w {
+                    let p = t.point(&Point2::new(x as f32 + 0.5, y as f32 + 0.5));
+                    let (sx, sy) = (f32::floor(p.x) as u32, f32::floor(p.y) as u32);
+                    assert_eq!(
+                        view.get_pixel(x, y),
+                        *image.get_pixel(sx, sy),
+                        "{} at ({}, {})",
+                        value,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exif() {
+        // a JPEG header with APP0 and APP1, whose TIFF is big endian.
+        let mut data = b"\xff\xd8\xff\xe0\x00\x04ab".to_vec();
+        let tiff =
+            b"MM\x00\x2a\x00\x00\x00\x08\x00\x01\x01\x12\x00\x03\x00\x00\x00\x01\x00\x06\x00\x00\x00\x00\x00\x00";
+        data.extend_from_slice(&[0xff, 0xe1, 0x00, (2 + 6 + tiff.len()) as u8]);
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend_from_slice(tiff);
+        data.extend_from_slice(b"\xff\xda");
+        assert_eq!(
+            Orientation::from_exif(&data),
+            Some(Orientation::new(Rotation::Cw90, false))
+        );
+        assert_eq!(Orientation::from_exif(&data[..data.len() - 10]), None);
+        assert_eq!(Orientation::from_exif(b"\xff\xd8\xff\xda"), None);
+    }
+}
@@ -0,0 +1,147 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::face_detector;
+use crate::face_landmark;
+use crate::face_loop;
+use crate::orientation;
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::*;
+
+#[derive(Clone, Debug)]
+pub struct PipelineResult {
+    // as given to Pipeline::push.
+    pub timestamp: time::Duration,
+    pub faces: Vec<face_landmark::FaceLandmarks>,
+    // from Pipeline::push to the start of the detection.
+    pub queue_latency: time::Duration,
+    // from Pipeline::push to the end of the landmarks.
+    pub latency: time::Duration,
+    // frames dropped since the previous result.
+    pub dropped: usize,
+}
+
+struct Frame {
+    image: image::RgbImage,
+    timestamp: time::Duration,
+    pushed: time::Instant,
+}
+
+#[derive(Default)]
+struct Queue {
+    frames: VecDeque<Frame>,
+    dropped: usize,
+    closed: bool,
+}
+
+// runs the detection of frame N + 1 and the landmarks of frame N in parallel.  stale frames are dropped, i.e.
+// the detector always takes the latest frame.
+pub struct Pipeline {
+    capacity: usize,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    results: mpsc::Receiver<PipelineResult>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Pipeline {
+    // the queue holds at most capacity frames, dropping the oldest ones.
+    pub fn new(config: face_loop::FaceLoopConfig, capacity: usize) -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let (detection_tx, detection_rx) = mpsc::sync_channel(1);
+        let (result_tx, result_rx) = mpsc::channel();
+
+        let detector_thread = {
+            let queue = queue.clone();
+            let orientation = config.orientation;
+            let config = config.detector_config();
+            thread::spawn(move || {
+                let detector = face_detector::FaceDetector::with_config(config);
+                while let Some((frame, dropped)) = pop_latest(&queue) {
+                    let queue_latency = frame.pushed.elapsed();
+                    // in the upright image, as FaceLoop::run_with_detections.
+                    let bboxes = detector.run(&orientation::Oriented::new(&frame.image, orientation));
+                    if detection_tx.send((frame, bboxes, queue_latency, dropped)).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+
+        let landmark_thread = thread::spawn(move || {
+            let config = face_loop::FaceLoopConfig {
+                mode: face_loop::Mode::StaticImage,
+                ..config
+            };
+            let mut face_loop = face_loop::FaceLoop::with_config(config);
+            for (frame, bboxes, queue_latency, dropped) in detection_rx.iter() {
+                let faces = face_loop.run_with_detections(&frame.image, bboxes);
+                let result = PipelineResult {
+                    timestamp: frame.timestamp,
+                    faces: faces,
+                    queue_latency: queue_latency,
+                    latency: frame.pushed.elapsed(),
+                    dropped: dropped,
+                };
+                if result_tx.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Pipeline {
+            capacity: cmp::max(capacity, 1),
+            queue: queue,
+            results: result_rx,
+            threads: vec![detector_thread, landmark_thread],
+        }
+    }
+
+    pub fn push(&self, image: image::RgbImage, timestamp: time::Duration) {
+        let (lock, cvar) = &*self.queue;
+        let mut queue = lock.lock().unwrap();
+        while queue.frames.len() >= self.capacity {
+            queue.frames.pop_front();
+            queue.dropped += 1;
+        }
+        queue.frames.push_back(Frame {
+            image: image,
+            timestamp: timestamp,
+            pushed: time::Instant::now(),
+        });
+        cvar.notify_one();
+    }
+
+    pub fn results(&self) -> &mpsc::Receiver<PipelineResult> {
+        &self.results
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        {
+            let (lock, cvar) = &*self.queue;
+            lock.lock().unwrap().closed = true;
+            cvar.notify_all();
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+// returns the latest frame and the number of dropped frames, or None if closed.
+fn pop_latest(queue: &(Mutex<Queue>, Condvar)) -> Option<(Frame, usize)> {
+    let (lock, cvar) = queue;
+    let mut queue = lock.lock().unwrap();
+    loop {
+        if queue.closed {
+            return None;
+        }
+        if let Some(frame) = queue.frames.pop_back() {
+            let dropped = queue.dropped + queue.frames.len();
+            queue.frames.clear();
+            queue.dropped = 0;
+            return Some((frame, dropped));
+        }
+        queue = cvar.wait(queue).unwrap();
+    }
+}
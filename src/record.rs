@@ -0,0 +1,177 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+// a versioned record of the results of a frame, for archiving, e.g. as JSON by serde_json.  the JSON schema is
+// schema/frame_record.v1.json.  within a version, fields are only added, as optional ones.  the matrices and the
+// points are in the serde layout of nalgebra, which is pinned in Cargo.toml for that, and the test pins the shape.
+use crate::coords;
+use crate::depth;
+use crate::face_landmark;
+use crate::head_pose;
+use std::*;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct FrameRecord {
+    // SCHEMA_VERSION of the writer.
+    pub version: u32,
+    // of the frame, e.g. pipeline::PipelineResult::timestamp, in microseconds.
+    pub timestamp_us: u64,
+    pub image_width: u32,
+    pub image_height: u32,
+    // of the points and boxes.
+    pub space: coords::CoordinateSpace,
+    pub faces: Vec<FaceRecord>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct FaceRecord {
+    // tracker::TrackEvent::id, if tracked.
+    #[serde(default)]
+    pub track_id: Option<u64>,
+    pub landmarks: face_landmark::FaceLandmarks,
+    #[serde(default)]
+    pub head_pose: Option<head_pose::HeadPose>,
+    #[serde(default)]
+    pub depth: Option<depth::Depth>,
+}
+
+impl FrameRecord {
+    // image_size is (height, width).  the faces are in space, e.g. face_loop::FaceLoopConfig::space.
+    pub fn new(
+        timestamp: time::Duration, image_size: (u32, u32), space: coords::CoordinateSpace,
+        faces: Vec<face_landmark::FaceLandmarks>,
+    ) -> Self {
+        FrameRecord {
+            version: SCHEMA_VERSION,
+            timestamp_us: timestamp.as_micros() as u64,
+            image_width: image_size.1,
+            image_height: image_size.0,
+            space: space,
+            faces: faces
+                .into_iter()
+                .map(|landmarks| FaceRecord {
+                    track_id: None,
+                    landmarks: landmarks,
+                    head_pose: None,
+                    depth: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn timestamp(&self) -> time::Duration {
+        time::Duration::from_micros(self.timestamp_us)
+    }
+
+    // i.e. written by the same major version of the schema.
+    pub fn is_supported(&self) -> bool {
+        self.version == SCHEMA_VERSION
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::affine::Affine2;
+    use crate::face_detector;
+    use crate::iris_landmark;
+    use nalgebra::{Matrix2, Matrix3, Point3, Vector2, Vector3};
+    use serde_json::json;
+
+    fn record() -> FrameRecord {
+        let eye = |x: f32| iris_landmark::Eye {
+            contour: vec![Point3::new(x, 2.0, 3.0); 71],
+            iris: vec![Point3::new(x, 5.0, 6.0); 5],
+        };
+        let landmarks = face_landmark::FaceLandmarks {
+            points: vec![Point3::new(1.0, 2.0, 3.0); 468],
+            presence: 0.75,
+            valid: true,
+            detection: Some(face_detector::BBox {
+                center: (10.0, 20.0),
+                size: (30.0, 40.0),
+                key_points: [(1.0, 2.0); 6],
+                score: 0.5,
+            }),
+            transform: Affine2::new(Matrix2::new(1.0, 2.0, 3.0, 4.0), Vector2::new(5.0, 6.0)),
+            eyes: Some([eye(-1.0), eye(1.0)]),
+            debug: None,
+        };
+        let mut record = FrameRecord::new(
+            time::Duration::from_millis(1500),
+            (480, 640),
+            coords::CoordinateSpace::Pixel,
+            vec![landmarks],
+        );
+        record.faces[0].track_id = Some(7);
+        record.faces[0].head_pose = Some(head_pose::HeadPose {
+            yaw: 10.0,
+            pitch: -5.0,
+            roll: 2.5,
+            rotation: Matrix3::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0),
+            translation: Vector3::new(1.0, 2.0, 30.0),
+            residual: 0.25,
+        });
+        record.faces[0].depth = Some(depth::Depth {
+            distance: 500.0,
+            eyes: [490.0, 510.0],
+            scale: 0.5,
+        });
+        record
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = serde_json::to_value(record()).unwrap();
+        let decoded: FrameRecord = serde_json::from_value(value.clone()).unwrap();
+        assert!(decoded.is_supported());
+        assert_eq!(decoded.timestamp(), time::Duration::from_millis(1500));
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+    }
+
+    // as schema/frame_record.v1.json.
+    #[test]
+    fn shape() {
+        let value = serde_json::to_value(record()).unwrap();
+        assert_eq!(value["version"], json!(1));
+        assert_eq!(value["timestamp_us"], json!(1500000));
+        assert_eq!(
+            (&value["image_width"], &value["image_height"]),
+            (&json!(640), &json!(480))
+        );
+        assert_eq!(value["space"], json!("Pixel"));
+
+        let face = &value["faces"][0];
+        assert_eq!(face["track_id"], json!(7));
+        let landmarks = &face["landmarks"];
+        assert_eq!(landmarks["points"].as_array().unwrap().len(), 468);
+        assert_eq!(landmarks["points"][0], json!([1.0, 2.0, 3.0]));
+        assert_eq!(landmarks["presence"], json!(0.75));
+        assert_eq!(landmarks["valid"], json!(true));
+        assert_eq!(landmarks["detection"]["center"], json!([10.0, 20.0]));
+        assert_eq!(landmarks["detection"]["size"], json!([30.0, 40.0]));
+        assert_eq!(landmarks["detection"]["key_points"][5], json!([1.0, 2.0]));
+        // column-major.
+        assert_eq!(landmarks["transform"]["linear"], json!([1.0, 3.0, 2.0, 4.0]));
+        assert_eq!(landmarks["transform"]["translation"], json!([5.0, 6.0]));
+        assert_eq!(landmarks["eyes"][1]["iris"][0], json!([1.0, 5.0, 6.0]));
+        assert_eq!(landmarks["eyes"][0]["contour"].as_array().unwrap().len(), 71);
+        assert!(landmarks.get("debug").is_none());
+
+        let pose = &face["head_pose"];
+        assert_eq!(pose["rotation"], json!([1.0, 4.0, 7.0, 2.0, 5.0, 8.0, 3.0, 6.0, 9.0]));
+        assert_eq!(pose["translation"], json!([1.0, 2.0, 30.0]));
+        assert_eq!(face["depth"]["eyes"], json!([490.0, 510.0]));
+    }
+
+    #[test]
+    fn optional_fields() {
+        let mut value = serde_json::to_value(record()).unwrap();
+        for key in ["track_id", "head_pose", "depth"].iter() {
+            value["faces"][0].as_object_mut().unwrap().remove(*key);
+        }
+        let decoded: FrameRecord = serde_json::from_value(value).unwrap();
+        let face = &decoded.faces[0];
+        assert!(face.track_id.is_none() && face.head_pose.is_none() && face.depth.is_none());
+    }
+}
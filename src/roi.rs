@@ -0,0 +1,295 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::affine::Affine2;
+use crate::face_detector::BBox;
+use crate::image_util;
+use crate::mesh;
+use nalgebra::{Point2, Point3};
+use std::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Square {
+    None,
+    Long,
+    Short,
+}
+
+// outside of the source image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Border {
+    Constant(image::Rgb<u8>),
+    Replicate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+}
+
+// of Roi::sample_into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampling {
+    pub border: Border,
+    pub interpolation: Interpolation,
+    pub normalization: image_util::Normalization,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            border: Border::Constant(image::Rgb([127, 127, 127])),
+            interpolation: Interpolation::Bilinear,
+            normalization: image_util::Normalization::default(),
+        }
+    }
+}
+
+// rotated rectangle in pixels, (y, x) order as BBox.
+#[derive(Clone, Debug)]
+pub struct Roi {
+    pub center: (f32, f32),
+    pub size: (f32, f32),
+    // clockwise in the image, ref. mediapipe/framework/formats/rect.proto.
+    pub rotation: f32,
+}
+
+impl Roi {
+    // ref. <https://github.com/google/mediapipe/blob/master/mediapipe/modules/face_landmark/face_detection_front_detection_to_roi.pbtxt>.
+    pub fn from_bbox(bbox: &BBox) -> Self {
+        Self::from_detection(bbox, (0, 1), 0.0).transform((1.5, 1.5), (0.0, 0.0), Square::Long)
+    }
+
+    // the box rotated so that key_points.0 -> key_points.1 is at target_angle (in degrees).
+    pub fn from_detection(bbox: &BBox, key_points: (usize, usize), target_angle: f32) -> Self {
+        Roi {
+            center: bbox.center,
+            size: bbox.size,
+            rotation: rotation(
+                bbox.key_points[key_points.0],
+                bbox.key_points[key_points.1],
+                target_angle,
+            ),
+        }
+    }
+
+    // ref. <https://github.com/google/mediapipe/blob/master/mediapipe/modules/face_landmark/face_landmarks_to_roi.pbtxt>.
+    pub fn from_landmarks(points: &[Point3<f32>]) -> Self {
+        Self::from_landmarks_bounds(points, 0.0).transform((1.5, 1.5), (0.0, 0.0), Square::Long)
+    }
+
+    // the bounding box of the landmarks, rotated so that the eyes are at target_angle (in degrees).
+    pub fn from_landmarks_bounds(points: &[Point3<f32>], target_angle: f32) -> Self {
+        let mut y0 = f32::INFINITY;
+        let mut x0 = f32::INFINITY;
+        let mut y1 = -f32::INFINITY;
+        let mut x1 = -f32::INFINITY;
+        for p in points.iter() {
+            y0 = f32::min(y0, p.y);
+            x0 = f32::min(x0, p.x);
+            y1 = f32::max(y1, p.y);
+            x1 = f32::max(x1, p.x);
+        }
+        let p0 = points[mesh::RIGHT_EYE_OUTER];
+        let p1 = points[mesh::LEFT_EYE_OUTER];
+        Roi {
+            center: ((y0 + y1) / 2.0, (x0 + x1) / 2.0),
+            size: (y1 - y0, x1 - x0),
+            rotation: rotation((p0.y, p0.x), (p1.y, p1.x), target_angle),
+        }
+    }
+
+    // the square rectangle around two points, rotated along p0 -> p1.
+    pub fn from_points(p0: (f32, f32), p1: (f32, f32), scale: f32) -> Self {
+        let roi = Roi {
+            center: ((p0.0 + p1.0) / 2.0, (p0.1 + p1.1) / 2.0),
+            size: (f32::abs(p1.0 - p0.0), f32::abs(p1.1 - p0.1)),
+            rotation: rotation(p0, p1, 0.0),
+        };
+        roi.transform((scale, scale), (0.0, 0.0), Square::Long)
+    }
+
+    // scale and shift are (y, x) relative to the size.
+    // ref. <https://github.com/google/mediapipe/blob/master/mediapipe/calculators/util/rect_transformation_calculator.cc>.
+    pub fn transform(&self, scale: (f32, f32), shift: (f32, f32), square: Square) -> Self {
+        let sin = f32::sin(self.rotation);
+        let cos = f32::cos(self.rotation);
+        let dy = self.size.0 * shift.0;
+        let dx = self.size.1 * shift.1;
+        let center = (self.center.0 + dx * sin + dy * cos, self.center.1 + dx * cos - dy * sin);
+
+        let size = match square {
+            Square::None => self.size,
+            Square::Long => {
+                let s = f32::max(self.size.0, self.size.1);
+                (s, s)
+            }
+            Square::Short => {
+                let s = f32::min(self.size.0, self.size.1);
+                (s, s)
+            }
+        };
+        Roi {
+            center: center,
+            size: (scale.0 * size.0, scale.1 * size.1),
+            rotation: self.rotation,
+        }
+    }
+
+    // returns the transforms from the source image to the cropped one and its inverse.  the cropped image is
+    // round(size) pixels, optionally flipped horizontally.
+    pub fn transforms(&self, flip: bool) -> (Affine2, Affine2) {
+        let h = f32::round(self.size.0);
+        let w = f32::round(self.size.1);
+        let f_transform = if flip {
+            Affine2::from_translation(w, 0.0) * Affine2::from_scale(-1.0, 1.0)
+        } else {
+            Affine2::identity()
+        };
+        let n_transform = f_transform
+            * Affine2::from_translation(w / 2.0, h / 2.0)
+            * Affine2::from_rotation(-self.rotation)
+            * Affine2::from_translation(-self.center.1, -self.center.0);
+        (n_transform, n_transform.inverse())
+    }
+
+    // returns the cropped image and the transform from it to the source image.
+    pub fn crop<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, flip: bool,
+    ) -> (image::RgbImage, Affine2) {
+        self.crop_with(
+            image,
+            flip,
+            Border::Constant(image::Rgb([127, 127, 127])),
+            Interpolation::Bilinear,
+        )
+    }
+
+    pub fn crop_with<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, image: &I, flip: bool, border: Border, interpolation: Interpolation,
+    ) -> (image::RgbImage, Affine2) {
+        let (_, i_transform) = self.transforms(flip);
+        let cropped = image::RgbImage::from_fn(
+            f32::round(self.size.1) as u32,
+            f32::round(self.size.0) as u32,
+            |x, y| {
+                let p = i_transform.point(&Point2::new(x as f32 + 0.5, y as f32 + 0.5));
+                sample(image, p.x - 0.5, p.y - 0.5, border, interpolation)
+            },
+        );
+        (cropped, i_transform)
+    }
+
+    // samples the ROI directly into a size x size RGB tensor in one pass, and returns the transform from the
+    // tensor to the source image.  both are in continuous coordinates, i.e. pixel (i, j) is centered at
+    // (i + 0.5, j + 0.5).
+    pub fn sample_into<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+        &self, dst: &mut [f32], size: usize, image: &I, flip: bool, sampling: &Sampling,
+    ) -> Affine2 {
+        let (_, i_transform) = self.transforms(flip);
+        let s = size as f32;
+        let transform = i_transform * Affine2::from_scale(f32::round(self.size.1) / s, f32::round(self.size.0) / s);
+        let (scale, bias) = sampling.normalization.scale_bias();
+        for y in 0..size {
+            let row = &mut dst[(3 * size) * y..(3 * size) * (y + 1)];
+            for x in 0..size {
+                let p = transform.point(&Point2::new(x as f32 + 0.5, y as f32 + 0.5));
+                let px = sample_f32(image, p.x - 0.5, p.y - 0.5, sampling.border, sampling.interpolation);
+                for c in 0..3 {
+                    row[3 * x + c] = px[c] * scale[c] + bias[c];
+                }
+            }
+        }
+        transform
+    }
+}
+
+pub fn sample<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+    image: &I, x: f32, y: f32, border: Border, interpolation: Interpolation,
+) -> image::Rgb<u8> {
+    let v = sample_f32(image, x, y, border, interpolation);
+    image::Rgb([f32::round(v[0]) as u8, f32::round(v[1]) as u8, f32::round(v[2]) as u8])
+}
+
+fn sample_f32<I: image::GenericImageView<Pixel = image::Rgb<u8>>>(
+    image: &I, x: f32, y: f32, border: Border, interpolation: Interpolation,
+) -> [f32; 3] {
+    let w = image.width() as i64;
+    let h = image.height() as i64;
+    let get = |x: i64, y: i64| -> [f32; 3] {
+        let px = if 0 <= x && x < w && 0 <= y && y < h {
+            image.get_pixel(x as u32, y as u32)
+        } else {
+            match border {
+                Border::Constant(c) => c,
+                Border::Replicate => image.get_pixel(x.clamp(0, w - 1) as u32, y.clamp(0, h - 1) as u32),
+            }
+        };
+        [px[0] as f32, px[1] as f32, px[2] as f32]
+    };
+
+    match interpolation {
+        Interpolation::Nearest => get(f32::round(x) as i64, f32::round(y) as i64),
+        Interpolation::Bilinear => {
+            let x0 = f32::floor(x);
+            let y0 = f32::floor(y);
+            let fx = x - x0;
+            let fy = y - y0;
+            let (x0, y0) = (x0 as i64, y0 as i64);
+            let p00 = get(x0, y0);
+            let p01 = get(x0 + 1, y0);
+            let p10 = get(x0, y0 + 1);
+            let p11 = get(x0 + 1, y0 + 1);
+            let mut v = [0.0; 3];
+            for c in 0..3 {
+                v[c] = (1.0 - fy) * ((1.0 - fx) * p00[c] + fx * p01[c]) + fy * ((1.0 - fx) * p10[c] + fx * p11[c]);
+            }
+            v
+        }
+    }
+}
+
+// p0 -> p1 becomes target_angle (in degrees) in the cropped image.
+fn rotation(p0: (f32, f32), p1: (f32, f32), target_angle: f32) -> f32 {
+    let r = target_angle.to_radians() - f32::atan2(-(p1.0 - p0.0), p1.1 - p0.1);
+    r - 2.0 * f32::consts::PI * f32::floor((r + f32::consts::PI) / (2.0 * f32::consts::PI))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_into() {
+        // linear, so bilinear sampling is exact.
+        let image = image::RgbImage::from_fn(4, 4, |x, y| image::Rgb([(10 * x) as u8, (20 * y) as u8, 200]));
+        let sampling = Sampling {
+            border: Border::Constant(image::Rgb([255, 0, 51])),
+            interpolation: Interpolation::Bilinear,
+            normalization: image_util::Normalization::Range(0.0, 1.0),
+        };
+        // twice the image, i.e. tensor pixel (i, j) is centered at image pixel (2 i - 1.5, 2 j - 1.5).
+        let roi = Roi {
+            center: (2.0, 2.0),
+            size: (8.0, 8.0),
+            rotation: 0.0,
+        };
+        let mut dst = vec![f32::NAN; 3 * 4 * 4];
+        let t = roi.sample_into(&mut dst, 4, &image, false, &sampling);
+        for y in 0..4 {
+            for x in 0..4 {
+                let e = if 1 <= y && y <= 2 && 1 <= x && x <= 2 {
+                    let (sx, sy) = (2.0 * x as f32 - 1.5, 2.0 * y as f32 - 1.5);
+                    [10.0 * sx / 255.0, 20.0 * sy / 255.0, 200.0 / 255.0]
+                } else {
+                    [1.0, 0.0, 0.2]
+                };
+                for c in 0..3 {
+                    let v = dst[3 * (4 * y + x) + c];
+                    assert!((v - e[c]).abs() < 1e-6, "({}, {}, {}): {} != {}", x, y, c, v, e[c]);
+                }
+            }
+        }
+        let p = t.point(&Point2::new(1.5, 2.5));
+        assert!((p - Point2::new(1.0, 3.0)).norm() < 1e-6);
+    }
+}
@@ -43,6 +43,9 @@ pub struct TensorRefMut<'a> {
     data: *mut c_void,
 }
 
+// the interpreter can be moved between threads, but not shared.
+unsafe impl Send for Interpreter {}
+
 impl Drop for Interpreter {
     fn drop(&mut self) {
         unsafe {
@@ -0,0 +1,450 @@
+// (c) Yasuhiro Fujii <http://mimosa-pudica.net>, under MIT License.
+use crate::face_detector;
+use crate::face_landmark;
+use crate::mesh;
+use nalgebra::Point3;
+use std::*;
+
+// landmarks stable under expressions, for the shape descriptor.
+const DESCRIPTOR_POINTS: [usize; 10] = [
+    mesh::FOREHEAD,
+    mesh::NOSE_TIP,
+    mesh::CHIN,
+    mesh::RIGHT_CHEEK,
+    mesh::LEFT_CHEEK,
+    mesh::RIGHT_EYE_OUTER,
+    mesh::RIGHT_EYE_INNER,
+    mesh::LEFT_EYE_OUTER,
+    mesh::LEFT_EYE_INNER,
+    mesh::MOUTH_RIGHT,
+];
+
+#[derive(Clone, Debug)]
+pub struct TrackerConfig {
+    // of the landmark bounding boxes between frames.
+    pub min_iou: f32,
+    // guards IoU matches against swapped faces.
+    pub max_shape_distance: f32,
+    // frames a track survives without matches, e.g. short occlusions.
+    pub max_missed: usize,
+    // frames a lost track is remembered for re-identification.
+    pub reid_memory: usize,
+    // of the RMS difference of the shape descriptors.  the proportions of different people often differ by only
+    // 0.05 or so, thus it is tight.
+    pub reid_threshold: f32,
+    // of the box center from the last one of the track, relative to the longer side of the box.  re-identification
+    // is position-gated by default, i.e. a face coming back far from where it was lost gets a new ID.  the shape
+    // alone is reliable only for a few people, but f32::INFINITY re-identifies faces anywhere in the image.
+    pub reid_max_shift: f32,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        TrackerConfig {
+            min_iou: 0.3,
+            max_shape_distance: 0.5,
+            max_missed: 15,
+            reid_memory: 300,
+            reid_threshold: 0.03,
+            reid_max_shift: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrackState {
+    New,
+    // matched, including a lost track re-identified.
+    Updated,
+    // not matched for TrackerConfig::max_missed frames.  emitted once.
+    Lost,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackEvent {
+    pub id: u64,
+    pub state: TrackState,
+    // the index of the face given to FaceTracker::update, None if lost.
+    pub face: Option<usize>,
+}
+
+struct Track {
+    id: u64,
+    // (center, size) of the landmarks, (y, x) order as face_detector::BBox.
+    bounds: ((f32, f32), (f32, f32)),
+    descriptor: Vec<f32>,
+    // frames since the last match.
+    missed: usize,
+}
+
+// assigns persistent IDs to the faces of successive frames, e.g. of face_loop::FaceLoop::run.
+pub struct FaceTracker {
+    config: TrackerConfig,
+    next_id: u64,
+    tracks: Vec<Track>,
+    lost: Vec<Track>,
+}
+
+impl FaceTracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        FaceTracker {
+            config: config,
+            next_id: 0,
+            tracks: Vec::new(),
+            lost: Vec::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+        self.lost.clear();
+    }
+
+    pub fn update(&mut self, faces: &[face_landmark::FaceLandmarks]) -> Vec<TrackEvent> {
+        let observations: Vec<_> = faces
+            .iter()
+            .map(|f| (bounds(&f.points), descriptor(&f.points)))
+            .collect();
+        let mut assigned: Vec<Option<u64>> = vec![None; faces.len()];
+        let mut events = Vec::new();
+
+        // active tracks by IoU.
+        let mut pairs = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (f, (b, d)) in observations.iter().enumerate() {
+                let iou = face_detector::iou(track.bounds.0, track.bounds.1, b.0, b.1);
+                if iou >= self.config.min_iou && distance(&track.descriptor, d) <= self.config.max_shape_distance {
+                    pairs.push((iou, t, f));
+                }
+            }
+        }
+        pairs.sort_by(|e0, e1| e1.0.partial_cmp(&e0.0).unwrap());
+        let mut matched = vec![false; self.tracks.len()];
+        for (_, t, f) in pairs.into_iter() {
+            if matched[t] || assigned[f].is_some() {
+                continue;
+            }
+            matched[t] = true;
+            assigned[f] = Some(self.tracks[t].id);
+            update_track(&mut self.tracks[t], observations[f].0, &observations[f].1);
+        }
+
+        // the rest of the active tracks and the lost ones by shape, near the last position.
+        let mut pairs = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate().filter(|(t, _)| !matched[*t]) {
+            for (f, (b, d)) in observations.iter().enumerate().filter(|(f, _)| assigned[*f].is_none()) {
+                if shift(&track.bounds, b) <= self.config.reid_max_shift {
+                    pairs.push((distance(&track.descriptor, d), false, t, f));
+                }
+            }
+        }
+        for (t, track) in self.lost.iter().enumerate() {
+            for (f, (b, d)) in observations.iter().enumerate().filter(|(f, _)| assigned[*f].is_none()) {
+                if shift(&track.bounds, b) <= self.config.reid_max_shift {
+                    pairs.push((distance(&track.descriptor, d), true, t, f));
+                }
+            }
+        }
+        pairs.retain(|e| e.0 <= self.config.reid_threshold);
+        pairs.sort_by(|e0, e1| e0.0.partial_cmp(&e1.0).unwrap());
+        let mut matched_lost = vec![false; self.lost.len()];
+        for (_, is_lost, t, f) in pairs.into_iter() {
+            let done = if is_lost { &mut matched_lost[t] } else { &mut matched[t] };
+            if *done || assigned[f].is_some() {
+                continue;
+            }
+            *done = true;
+            let track = if is_lost {
+                &mut self.lost[t]
+            } else {
+                &mut self.tracks[t]
+            };
+            assigned[f] = Some(track.id);
+            update_track(track, observations[f].0, &observations[f].1);
+        }
+
+        // moves tracks between the active and the lost.
+        let mut tracks = Vec::new();
+        for (track, m) in self.tracks.drain(..).zip(matched) {
+            if m {
+                tracks.push(track);
+            } else if track.missed + 1 > self.config.max_missed {
+                events.push(TrackEvent {
+                    id: track.id,
+                    state: TrackState::Lost,
+                    face: None,
+                });
+                self.lost.push(Track { missed: 0, ..track });
+            } else {
+                tracks.push(Track {
+                    missed: track.missed + 1,
+                    ..track
+                });
+            }
+        }
+        let mut lost = Vec::new();
+        for (track, m) in self
+            .lost
+            .drain(..)
+            .zip(matched_lost.into_iter().chain(iter::repeat(false)))
+        {
+            if m {
+                tracks.push(track);
+            } else if track.missed < self.config.reid_memory {
+                lost.push(Track {
+                    missed: track.missed + 1,
+                    ..track
+                });
+            }
+        }
+        self.lost = lost;
+
+        for (f, id) in assigned.iter_mut().enumerate() {
+            let state = match id {
+                Some(_) => TrackState::Updated,
+                None => {
+                    *id = Some(self.next_id);
+                    self.next_id += 1;
+                    tracks.push(Track {
+                        id: id.unwrap(),
+                        bounds: observations[f].0,
+                        descriptor: observations[f].1.clone(),
+                        missed: 0,
+                    });
+                    TrackState::New
+                }
+            };
+            events.push(TrackEvent {
+                id: id.unwrap(),
+                state: state,
+                face: Some(f),
+            });
+        }
+        self.tracks = tracks;
+        events
+    }
+}
+
+fn update_track(track: &mut Track, bounds: ((f32, f32), (f32, f32)), descriptor: &[f32]) {
+    track.bounds = bounds;
+    for (d, o) in track.descriptor.iter_mut().zip(descriptor.iter()) {
+        *d = 0.9 * *d + 0.1 * o;
+    }
+    track.missed = 0;
+}
+
+fn bounds(points: &[Point3<f32>]) -> ((f32, f32), (f32, f32)) {
+    let mut min = (f32::INFINITY, f32::INFINITY);
+    let mut max = (-f32::INFINITY, -f32::INFINITY);
+    for p in points.iter() {
+        min = (f32::min(min.0, p.y), f32::min(min.1, p.x));
+        max = (f32::max(max.0, p.y), f32::max(max.1, p.x));
+    }
+    (
+        ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0),
+        (max.0 - min.0, max.1 - min.1),
+    )
+}
+
+// of the centers, relative to the longer side of b0.
+fn shift(b0: &((f32, f32), (f32, f32)), b1: &((f32, f32), (f32, f32))) -> f32 {
+    let dy = b1.0 .0 - b0.0 .0;
+    let dx = b1.0 .1 - b0.0 .1;
+    f32::hypot(dy, dx) / f32::max(f32::max(b0.1 .0, b0.1 .1), f32::EPSILON)
+}
+
+// pairwise 3D distances between DESCRIPTOR_POINTS, relative to the distance between the outer eye corners.
+fn descriptor(points: &[Point3<f32>]) -> Vec<f32> {
+    let scale = nalgebra::distance(&points[mesh::RIGHT_EYE_OUTER], &points[mesh::LEFT_EYE_OUTER]);
+    let mut dst = Vec::new();
+    for (i, p0) in DESCRIPTOR_POINTS.iter().enumerate() {
+        for p1 in DESCRIPTOR_POINTS[i + 1..].iter() {
+            dst.push(nalgebra::distance(&points[*p0], &points[*p1]) / f32::max(scale, f32::EPSILON));
+        }
+    }
+    dst
+}
+
+// RMS difference.
+fn distance(d0: &[f32], d1: &[f32]) -> f32 {
+    let sum: f32 = d0.iter().zip(d1.iter()).map(|(a, b)| (a - b) * (a - b)).sum();
+    f32::sqrt(sum / d0.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::affine::Affine2;
+
+    // DESCRIPTOR_POINTS in the unit of the distance between the outer eye corners, x right and y down.
+    const PERSON_A: [(f32, f32, f32); 10] = [
+        (0.0, -0.6, 0.1),
+        (0.0, 0.45, 0.35),
+        (0.0, 1.1, 0.1),
+        (-0.65, 0.35, -0.2),
+        (0.65, 0.35, -0.2),
+        (-0.5, 0.0, 0.0),
+        (-0.15, 0.0, 0.05),
+        (0.5, 0.0, 0.0),
+        (0.15, 0.0, 0.05),
+        (-0.25, 0.75, 0.1),
+    ];
+
+    // slightly different proportions, by about 0.06 of the descriptor.
+    const PERSON_B: [(f32, f32, f32); 10] = [
+        (0.0, -0.6, 0.1),
+        (0.0, 0.5, 0.3),
+        (0.0, 1.2, 0.1),
+        (-0.7, 0.4, -0.2),
+        (0.7, 0.4, -0.2),
+        (-0.5, 0.0, 0.0),
+        (-0.18, 0.0, 0.05),
+        (0.5, 0.0, 0.0),
+        (0.18, 0.0, 0.05),
+        (-0.28, 0.8, 0.1),
+    ];
+
+    // the box is of size x size pixels around the center (y, x).
+    fn face(person: &[(f32, f32, f32); 10], center: (f32, f32), size: f32) -> face_landmark::FaceLandmarks {
+        let corner = |i: usize| {
+            let (sy, sx) = [(-0.5, -0.5), (-0.5, 0.5), (0.5, -0.5), (0.5, 0.5)][i % 4];
+            Point3::new(center.1 + sx * size, center.0 + sy * size, 0.0)
+        };
+        let mut points: Vec<_> = (0..mesh::NUM_POINTS).map(corner).collect();
+        for (i, p) in DESCRIPTOR_POINTS.iter().zip(person.iter()) {
+            let s = size / 3.0;
+            points[*i] = Point3::new(center.1 + s * p.0, center.0 + s * p.1, s * p.2);
+        }
+        face_landmark::FaceLandmarks {
+            points: points,
+            presence: 1.0,
+            valid: true,
+            detection: None,
+            transform: Affine2::identity(),
+            eyes: None,
+            debug: None,
+        }
+    }
+
+    fn event(id: u64, state: TrackState, face: Option<usize>) -> (u64, TrackState, Option<usize>) {
+        (id, state, face)
+    }
+
+    fn update(
+        tracker: &mut FaceTracker, faces: &[face_landmark::FaceLandmarks],
+    ) -> Vec<(u64, TrackState, Option<usize>)> {
+        tracker
+            .update(faces)
+            .into_iter()
+            .map(|e| (e.id, e.state, e.face))
+            .collect()
+    }
+
+    #[test]
+    fn iou_continuity() {
+        let mut tracker = FaceTracker::new(TrackerConfig::default());
+        let faces = [
+            face(&PERSON_A, (100.0, 100.0), 90.0),
+            face(&PERSON_B, (100.0, 300.0), 90.0),
+        ];
+        let events = update(&mut tracker, &faces);
+        assert_eq!(
+            events,
+            [event(0, TrackState::New, Some(0)), event(1, TrackState::New, Some(1))]
+        );
+
+        // moving toward each other, in the reverse order.
+        for i in 1..20 {
+            let d = 5.0 * i as f32;
+            let faces = [
+                face(&PERSON_B, (100.0, 300.0 - d), 90.0),
+                face(&PERSON_A, (100.0, 100.0 + d), 90.0),
+            ];
+            let events = update(&mut tracker, &faces);
+            assert_eq!(
+                events,
+                [
+                    event(1, TrackState::Updated, Some(0)),
+                    event(0, TrackState::Updated, Some(1))
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn occlusion() {
+        let config = TrackerConfig::default();
+        let mut tracker = FaceTracker::new(config.clone());
+        assert_eq!(
+            update(&mut tracker, &[face(&PERSON_A, (100.0, 100.0), 90.0)]),
+            [event(0, TrackState::New, Some(0))]
+        );
+
+        for _ in 0..config.max_missed {
+            assert_eq!(update(&mut tracker, &[]), []);
+        }
+        assert_eq!(update(&mut tracker, &[]), [event(0, TrackState::Lost, None)]);
+        for _ in 0..10 {
+            assert_eq!(update(&mut tracker, &[]), []);
+        }
+
+        // re-identified near the last position.
+        let faces = [face(&PERSON_A, (110.0, 130.0), 100.0)];
+        assert_eq!(update(&mut tracker, &faces), [event(0, TrackState::Updated, Some(0))]);
+    }
+
+    #[test]
+    fn reid_anywhere() {
+        let config = TrackerConfig {
+            reid_max_shift: f32::INFINITY,
+            ..TrackerConfig::default()
+        };
+        let mut tracker = FaceTracker::new(config.clone());
+        update(&mut tracker, &[face(&PERSON_A, (100.0, 100.0), 90.0)]);
+        for _ in 0..config.max_missed + 1 {
+            update(&mut tracker, &[]);
+        }
+
+        // another person in place of the lost one, and the same person far from the last position.
+        let faces = [
+            face(&PERSON_B, (100.0, 100.0), 90.0),
+            face(&PERSON_A, (300.0, 500.0), 120.0),
+        ];
+        assert_eq!(
+            update(&mut tracker, &faces),
+            [
+                event(1, TrackState::New, Some(0)),
+                event(0, TrackState::Updated, Some(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn new_face() {
+        let config = TrackerConfig::default();
+        let mut tracker = FaceTracker::new(config.clone());
+        update(&mut tracker, &[face(&PERSON_A, (100.0, 100.0), 90.0)]);
+        for _ in 0..config.max_missed + 1 {
+            update(&mut tracker, &[]);
+        }
+
+        // another person in place of the lost one.
+        let faces = [face(&PERSON_B, (100.0, 100.0), 90.0)];
+        assert_eq!(update(&mut tracker, &faces), [event(1, TrackState::New, Some(0))]);
+
+        // the same person far from the last position, beyond reid_max_shift.
+        let faces = [
+            face(&PERSON_B, (100.0, 100.0), 90.0),
+            face(&PERSON_A, (100.0, 400.0), 90.0),
+        ];
+        assert_eq!(
+            update(&mut tracker, &faces),
+            [
+                event(1, TrackState::Updated, Some(0)),
+                event(2, TrackState::New, Some(1))
+            ]
+        );
+    }
+}